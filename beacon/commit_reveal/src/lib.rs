@@ -0,0 +1,253 @@
+//! A self-contained commit-reveal `RandomBeacon`, driven only by the
+//! epoch clock (no external chain or oracle required).
+//!
+//! Each epoch pipelines two rounds: the round opened for epoch `e` accepts
+//! `commit`s while `e` is the current epoch, then moves into its reveal
+//! phase for the whole of epoch `e + 1`, and is finalized (producing
+//! `get_beacon(e)`) the moment epoch `e + 2` begins. A commit or reveal
+//! submitted outside its round's open window is rejected outright, so a
+//! late reveal can never retroactively change an already-open round.
+extern crate ekiden_beacon_base;
+extern crate ekiden_common;
+
+#[macro_use]
+extern crate log;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ekiden_beacon_base::RandomBeacon;
+use ekiden_common::bytes::B256;
+use ekiden_common::epochtime::{EpochTime, TimeSourceNotifier};
+use ekiden_common::error::{Error, Result};
+use ekiden_common::futures::cpupool::CpuPool;
+use ekiden_common::futures::{future, BoxFuture, BoxStream, Stream};
+use ekiden_common::hash::hash;
+use ekiden_common::subscribers::StreamSubscribers;
+
+/// Error codes returned (as the `message` of an `Error`) by
+/// `CommitRevealBeacon`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCodes {
+    /// `commit` was called for a round that is not currently accepting
+    /// commitments (too early, or its commit window has already closed).
+    CommitWindowClosed,
+    /// `participant` has already committed in this round.
+    AlreadyCommitted,
+    /// `reveal` was called for a round that is not currently accepting
+    /// reveals (too early, or its reveal window has already closed).
+    RevealWindowClosed,
+    /// `participant` never submitted a commitment in this round.
+    NoCommitment,
+    /// The revealed `(entropy, nonce)` does not hash to the commitment
+    /// `participant` submitted for this round.
+    InvalidReveal,
+    /// `get_beacon` was called for an epoch that has not finalized yet.
+    BeaconNotAvailable,
+}
+
+impl ::std::fmt::Display for ErrorCodes {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let s = match self {
+            &ErrorCodes::CommitWindowClosed => "CommitWindowClosed",
+            &ErrorCodes::AlreadyCommitted => "AlreadyCommitted",
+            &ErrorCodes::RevealWindowClosed => "RevealWindowClosed",
+            &ErrorCodes::NoCommitment => "NoCommitment",
+            &ErrorCodes::InvalidReveal => "InvalidReveal",
+            &ErrorCodes::BeaconNotAvailable => "BeaconNotAvailable",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// In-flight commitments and reveals for a single epoch's round.
+#[derive(Default)]
+struct Round {
+    commitments: HashMap<B256, B256>,
+    reveals: HashMap<B256, B256>,
+}
+
+struct State {
+    /// Round currently accepting `commit`s, if any.
+    committing: Option<(EpochTime, Round)>,
+    /// Round currently accepting `reveal`s, if any.
+    revealing: Option<(EpochTime, Round)>,
+    /// Finalized entropy for every epoch processed so far.
+    beacons: HashMap<EpochTime, B256>,
+    /// Entropy of the most recently finalized epoch, chained into the
+    /// low-participation fallback so that outcome is still unpredictable
+    /// ahead of time (rather than e.g. always hashing to the same value).
+    last_entropy: B256,
+}
+
+struct Inner {
+    /// Minimum number of valid reveals a round needs before its entropy is
+    /// derived from them; below this, the deterministic fallback is used
+    /// instead.
+    reveal_threshold: usize,
+    state: Mutex<State>,
+    events: StreamSubscribers<(EpochTime, B256)>,
+}
+
+impl Inner {
+    fn commit(&self, epoch: EpochTime, participant: B256, commitment: B256) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match &mut state.committing {
+            Some((committing_epoch, round)) if *committing_epoch == epoch => {
+                if round.commitments.contains_key(&participant) {
+                    return Err(Error::new(ErrorCodes::AlreadyCommitted.to_string()));
+                }
+                round.commitments.insert(participant, commitment);
+                Ok(())
+            }
+            _ => Err(Error::new(ErrorCodes::CommitWindowClosed.to_string())),
+        }
+    }
+
+    fn reveal(&self, epoch: EpochTime, participant: B256, entropy: B256, nonce: B256) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match &mut state.revealing {
+            Some((revealing_epoch, round)) if *revealing_epoch == epoch => {
+                let commitment = round
+                    .commitments
+                    .get(&participant)
+                    .cloned()
+                    .ok_or_else(|| Error::new(ErrorCodes::NoCommitment.to_string()))?;
+
+                let mut preimage = entropy.to_vec();
+                preimage.extend_from_slice(&nonce.to_vec());
+                if hash(&preimage) != commitment {
+                    return Err(Error::new(ErrorCodes::InvalidReveal.to_string()));
+                }
+
+                round.reveals.insert(participant, entropy);
+                Ok(())
+            }
+            _ => Err(Error::new(ErrorCodes::RevealWindowClosed.to_string())),
+        }
+    }
+
+    /// Advance the epoch clock to `epoch`: finalize the round that was
+    /// revealing (it is now too late to reveal further into it), promote
+    /// the committing round into the reveal phase, and open a fresh round
+    /// to accept commits for `epoch`.
+    fn advance_epoch(&self, epoch: EpochTime) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some((revealing_epoch, round)) = state.revealing.take() {
+            let mut revealed: Vec<&B256> = round.reveals.keys().collect();
+            // Canonical order: every participant derives the same entropy
+            // regardless of the order reveals arrived in.
+            revealed.sort();
+
+            let entropy = if revealed.len() >= self.reveal_threshold {
+                let mut preimage = Vec::with_capacity(revealed.len() * 32);
+                for participant in &revealed {
+                    preimage.extend_from_slice(&round.reveals[*participant].to_vec());
+                }
+                hash(&preimage)
+            } else {
+                warn!(
+                    "Only {} of a required {} participants revealed for epoch {}; \
+                     falling back to deterministic entropy",
+                    revealed.len(),
+                    self.reveal_threshold,
+                    revealing_epoch
+                );
+                let mut preimage = state.last_entropy.to_vec();
+                preimage.extend_from_slice(&revealing_epoch.to_string().into_bytes());
+                hash(&preimage)
+            };
+
+            state.last_entropy = entropy;
+            state.beacons.insert(revealing_epoch, entropy);
+            self.events.notify(&(revealing_epoch, entropy));
+        }
+
+        state.revealing = state.committing.take();
+        state.committing = Some((epoch, Round::default()));
+
+        Ok(())
+    }
+
+    fn get_beacon(&self, epoch: EpochTime) -> Result<B256> {
+        self.state
+            .lock()
+            .unwrap()
+            .beacons
+            .get(&epoch)
+            .cloned()
+            .ok_or_else(|| Error::new(ErrorCodes::BeaconNotAvailable.to_string()))
+    }
+}
+
+/// A `RandomBeacon` whose entropy comes from a commit-reveal protocol run
+/// entirely by its caller (e.g. the consensus committee), rather than from
+/// an external chain or oracle.
+pub struct CommitRevealBeacon {
+    inner: Arc<Inner>,
+    time_notifier: Arc<TimeSourceNotifier>,
+}
+
+impl CommitRevealBeacon {
+    /// Construct a beacon that requires at least `reveal_threshold` valid
+    /// reveals per round before trusting them, falling back to
+    /// deterministic entropy otherwise.
+    pub fn new(time_notifier: Arc<TimeSourceNotifier>, reveal_threshold: usize) -> Result<Self> {
+        Ok(Self {
+            inner: Arc::new(Inner {
+                reveal_threshold,
+                state: Mutex::new(State {
+                    committing: None,
+                    revealing: None,
+                    beacons: HashMap::new(),
+                    last_entropy: B256::zero(),
+                }),
+                events: StreamSubscribers::new(),
+            }),
+            time_notifier,
+        })
+    }
+
+    /// Subscribe to the epoch clock on `executor`, advancing the
+    /// commit-reveal pipeline at the start of every new epoch.
+    pub fn start(&self, executor: &mut CpuPool) {
+        let inner = self.inner.clone();
+        executor.spawn(self.time_notifier.watch_epochs().for_each(move |epoch| {
+            if let Err(error) = inner.advance_epoch(epoch) {
+                error!("Failed to advance commit-reveal beacon: {}", error.message);
+            }
+            future::ok(())
+        }));
+    }
+
+    /// Submit a commitment `H(entropy || nonce)` for `epoch`'s round.
+    /// Only valid while `epoch` is the currently open commit round.
+    pub fn commit(&self, epoch: EpochTime, participant: B256, commitment: B256) -> Result<()> {
+        self.inner.commit(epoch, participant, commitment)
+    }
+
+    /// Reveal the `(entropy, nonce)` behind a prior commitment for
+    /// `epoch`'s round. Only valid while `epoch` is the currently open
+    /// reveal round, and only if it matches the stored commitment.
+    pub fn reveal(&self, epoch: EpochTime, participant: B256, entropy: B256, nonce: B256) -> Result<()> {
+        self.inner.reveal(epoch, participant, entropy, nonce)
+    }
+
+    /// Advance the epoch clock to `epoch` directly, bypassing
+    /// `time_notifier`. Exposed so that callers driving their own epoch
+    /// transitions (or tests) don't need a live `TimeSourceNotifier`.
+    pub fn advance_epoch(&self, epoch: EpochTime) -> Result<()> {
+        self.inner.advance_epoch(epoch)
+    }
+}
+
+impl RandomBeacon for CommitRevealBeacon {
+    fn watch_beacons(&self) -> BoxStream<(EpochTime, B256)> {
+        self.inner.events.subscribe().1
+    }
+
+    fn get_beacon(&self, epoch: EpochTime) -> BoxFuture<B256> {
+        Box::new(future::result(self.inner.get_beacon(epoch)))
+    }
+}
@@ -0,0 +1,120 @@
+extern crate ekiden_beacon_base;
+extern crate ekiden_beacon_commit_reveal;
+extern crate ekiden_common;
+
+#[macro_use]
+extern crate log;
+
+use ekiden_beacon_base::RandomBeacon;
+use ekiden_beacon_commit_reveal::{CommitRevealBeacon, ErrorCodes};
+use ekiden_common::bytes::B256;
+use ekiden_common::epochtime::local::{LocalTimeSourceNotifier, SystemTimeSource};
+use ekiden_common::futures::Future;
+use ekiden_common::hash::hash;
+use ekiden_common::testing::try_init_logging;
+use std::sync::Arc;
+
+fn commitment_for(entropy: &B256, nonce: &B256) -> B256 {
+    let mut preimage = entropy.to_vec();
+    preimage.extend_from_slice(&nonce.to_vec());
+    hash(&preimage)
+}
+
+fn new_beacon(reveal_threshold: usize) -> CommitRevealBeacon {
+    let time_source = Arc::new(SystemTimeSource {});
+    let time_notifier = Arc::new(LocalTimeSourceNotifier::new(time_source));
+    CommitRevealBeacon::new(time_notifier, reveal_threshold).unwrap()
+}
+
+#[test]
+fn test_commit_reveal_beacon() {
+    try_init_logging();
+
+    let beacon = new_beacon(2);
+
+    let alice = B256::from_slice(&[1; 32]);
+    let bob = B256::from_slice(&[2; 32]);
+
+    let alice_entropy = B256::from_slice(&[0xaa; 32]);
+    let alice_nonce = B256::from_slice(&[0xab; 32]);
+    let bob_entropy = B256::from_slice(&[0xbb; 32]);
+    let bob_nonce = B256::from_slice(&[0xbc; 32]);
+
+    // Epoch 0: open the first commit round.
+    beacon.advance_epoch(0).unwrap();
+    beacon
+        .commit(0, alice, commitment_for(&alice_entropy, &alice_nonce))
+        .unwrap();
+    beacon
+        .commit(0, bob, commitment_for(&bob_entropy, &bob_nonce))
+        .unwrap();
+
+    debug!("a reveal before the commit round enters its reveal phase should fail");
+    match beacon.reveal(0, alice, alice_entropy, alice_nonce) {
+        Err(e) => assert_eq!(e.message, ErrorCodes::RevealWindowClosed.to_string()),
+        Ok(()) => panic!("reveal should have failed (RevealWindowClosed)"),
+    }
+
+    // Epoch 1: epoch 0's round enters its reveal phase; a new commit
+    // round opens for epoch 1.
+    beacon.advance_epoch(1).unwrap();
+    beacon.reveal(0, alice, alice_entropy, alice_nonce).unwrap();
+    beacon.reveal(0, bob, bob_entropy, bob_nonce).unwrap();
+
+    debug!("a mismatched reveal should be rejected");
+    match beacon.reveal(0, alice, bob_entropy, alice_nonce) {
+        Err(e) => assert_eq!(e.message, ErrorCodes::InvalidReveal.to_string()),
+        Ok(()) => panic!("reveal should have failed (InvalidReveal)"),
+    }
+
+    debug!("get_beacon for an unfinalized epoch should fail");
+    assert!(beacon.get_beacon(0).wait().is_err());
+
+    // Subscribe before finalizing: `watch_beacons` only carries beacons
+    // finalized after the subscription is made.
+    let watch = beacon.watch_beacons();
+
+    // Epoch 2: epoch 0's round finalizes.
+    beacon.advance_epoch(2).unwrap();
+
+    debug!("a late reveal after finalization should fail");
+    match beacon.reveal(0, alice, alice_entropy, alice_nonce) {
+        Err(e) => assert_eq!(e.message, ErrorCodes::RevealWindowClosed.to_string()),
+        Ok(()) => panic!("reveal should have failed (RevealWindowClosed)"),
+    }
+
+    let entropy = beacon.get_beacon(0).wait().unwrap();
+
+    let mut expected_preimage = alice_entropy.to_vec();
+    expected_preimage.extend_from_slice(&bob_entropy.to_vec());
+    assert_eq!(entropy, hash(&expected_preimage));
+
+    let watched: Vec<(u64, B256)> = watch.take(1).collect().wait().unwrap();
+    assert_eq!(watched, vec![(0, entropy)]);
+}
+
+#[test]
+fn test_commit_reveal_beacon_fallback() {
+    try_init_logging();
+
+    // Require 2 reveals per round, but only ever get 1 -- every round
+    // should fall back to deterministic entropy instead of stalling.
+    let beacon = new_beacon(2);
+
+    let alice = B256::from_slice(&[1; 32]);
+    let entropy = B256::from_slice(&[0xaa; 32]);
+    let nonce = B256::from_slice(&[0xab; 32]);
+
+    beacon.advance_epoch(0).unwrap();
+    beacon
+        .commit(0, alice, commitment_for(&entropy, &nonce))
+        .unwrap();
+
+    beacon.advance_epoch(1).unwrap();
+    beacon.reveal(0, alice, entropy, nonce).unwrap();
+
+    beacon.advance_epoch(2).unwrap();
+    // Falling back still produces a value -- `get_beacon` never leaves a
+    // hole just because participation was too low to trust.
+    assert!(beacon.get_beacon(0).wait().is_ok());
+}
@@ -1,58 +1,221 @@
 //! Address defintion and helpers.
 use std::convert::TryFrom;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
 
 use error::Error;
 
 use ekiden_common_api as api;
 
 /// Address represents a public location that can be used to connect to an entity in ekiden.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct Address(SocketAddr);
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Address {
+    /// A raw socket address, dialed directly over TCP.
+    Tcp(SocketAddr),
+    /// A DNS name and port. Resolved to a `SocketAddr` on demand via
+    /// `resolve`, which caches the result so repeated dials don't
+    /// re-resolve the name every time. The cache is behind an `Arc<Mutex<..>>`,
+    /// not a `Cell`, so `Address` stays `Send + Sync` and usable from the
+    /// multi-threaded futures this codebase dials addresses from; cloning
+    /// an `Address` shares its resolved cache with the clone, the same as
+    /// cloning any other `Arc`-backed handle here.
+    Hostname {
+        host: String,
+        port: u16,
+        #[serde(skip)]
+        resolved: Arc<Mutex<Option<SocketAddr>>>,
+    },
+    /// An HTTP-upgrade (WebSocket) endpoint, for entities only reachable
+    /// through an intervening proxy that a raw TCP dial can't get
+    /// through. `secure` distinguishes `wss` from `ws`.
+    WebSocket {
+        host: String,
+        port: u16,
+        secure: bool,
+        #[serde(skip)]
+        resolved: Arc<Mutex<Option<SocketAddr>>>,
+    },
+}
+
+impl Address {
+    /// Resolve this address to a dialable `SocketAddr`, caching the
+    /// result so repeated calls don't re-resolve a hostname each time.
+    pub fn resolve(&self) -> Result<SocketAddr, Error> {
+        match self {
+            Address::Tcp(addr) => Ok(*addr),
+            Address::Hostname {
+                host,
+                port,
+                resolved,
+            } => Address::resolve_hostname(host, *port, resolved),
+            Address::WebSocket {
+                host,
+                port,
+                resolved,
+                ..
+            } => Address::resolve_hostname(host, *port, resolved),
+        }
+    }
+
+    fn resolve_hostname(
+        host: &str,
+        port: u16,
+        resolved: &Mutex<Option<SocketAddr>>,
+    ) -> Result<SocketAddr, Error> {
+        let mut resolved = resolved.lock().unwrap();
+        if let Some(addr) = *resolved {
+            return Ok(addr);
+        }
+        let addr = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| Error::new(&format!("Failed to resolve {}:{}: {}", host, port, e)))?
+            .next()
+            .ok_or_else(|| Error::new(&format!("No addresses found for {}:{}", host, port)))?;
+        *resolved = Some(addr);
+        Ok(addr)
+    }
+}
+
+/// Two addresses are equal if they refer to the same endpoint, regardless
+/// of whether either side has resolved (and cached) a `SocketAddr` yet.
+impl PartialEq for Address {
+    fn eq(&self, other: &Address) -> bool {
+        match (self, other) {
+            (Address::Tcp(a), Address::Tcp(b)) => a == b,
+            (
+                Address::Hostname { host: h1, port: p1, .. },
+                Address::Hostname { host: h2, port: p2, .. },
+            ) => h1 == h2 && p1 == p2,
+            (
+                Address::WebSocket {
+                    host: h1,
+                    port: p1,
+                    secure: s1,
+                    ..
+                },
+                Address::WebSocket {
+                    host: h2,
+                    port: p2,
+                    secure: s2,
+                    ..
+                },
+            ) => h1 == h2 && p1 == p2 && s1 == s2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Address {}
+
+impl Hash for Address {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Address::Tcp(addr) => {
+                0u8.hash(state);
+                addr.hash(state);
+            }
+            Address::Hostname { host, port, .. } => {
+                1u8.hash(state);
+                host.hash(state);
+                port.hash(state);
+            }
+            Address::WebSocket {
+                host, port, secure, ..
+            } => {
+                2u8.hash(state);
+                host.hash(state);
+                port.hash(state);
+                secure.hash(state);
+            }
+        }
+    }
+}
 
 impl TryFrom<api::Address> for Address {
     /// try_from Converts a protobuf `common::api::Address` into an address.
     type Error = super::error::Error;
     fn try_from(a: api::Address) -> Result<Self, Error> {
-        let ip = a.get_address();
-        let addr = match a.get_transport() {
+        let raw_port = a.get_port();
+        if raw_port > u16::max_value() as u32 {
+            return Err(Error::new("Port number out of range."));
+        }
+        let port = raw_port as u16;
+
+        match a.get_transport() {
             api::Address_Transport::TCPv4 => {
+                let ip = a.get_address();
                 let mut v4: [u8; 4] = Default::default();
                 if ip.len() != 4 {
                     return Err(Error::new("Invalid IP length."));
                 }
                 v4.copy_from_slice(&ip[0..4]);
-                IpAddr::V4(Ipv4Addr::from(v4))
+                Ok(Address::Tcp(SocketAddr::new(
+                    IpAddr::V4(Ipv4Addr::from(v4)),
+                    port,
+                )))
             }
             api::Address_Transport::TCPv6 => {
+                let ip = a.get_address();
                 let mut v6: [u8; 16] = Default::default();
                 if ip.len() != 16 {
                     return Err(Error::new("Invalid IP length."));
                 }
                 v6.copy_from_slice(&ip[0..16]);
-                IpAddr::V6(Ipv6Addr::from(v6))
+                Ok(Address::Tcp(SocketAddr::new(
+                    IpAddr::V6(Ipv6Addr::from(v6)),
+                    port,
+                )))
             }
-        };
-        // TODO: currently just ignore data set in upper half of port. should error.
-        let port = a.get_port();
-        Ok(Address(SocketAddr::new(addr, port as u16)))
+            api::Address_Transport::Hostname => Ok(Address::Hostname {
+                host: a.get_hostname().to_string(),
+                port: port,
+                resolved: Arc::new(Mutex::new(None)),
+            }),
+            api::Address_Transport::WebSocket => Ok(Address::WebSocket {
+                host: a.get_hostname().to_string(),
+                port: port,
+                secure: a.get_secure(),
+                resolved: Arc::new(Mutex::new(None)),
+            }),
+        }
     }
 }
 
 impl Into<api::Address> for Address {
     fn into(self) -> api::Address {
         let mut a = api::Address::new();
-        match self.0.ip() {
-            IpAddr::V4(ip) => {
-                a.set_transport(api::Address_Transport::TCPv4);
-                a.set_address(ip.octets().to_vec());
+        match self {
+            Address::Tcp(addr) => {
+                match addr.ip() {
+                    IpAddr::V4(ip) => {
+                        a.set_transport(api::Address_Transport::TCPv4);
+                        a.set_address(ip.octets().to_vec());
+                    }
+                    IpAddr::V6(ip) => {
+                        a.set_transport(api::Address_Transport::TCPv6);
+                        a.set_address(ip.octets().to_vec());
+                    }
+                }
+                a.set_port(addr.port().into());
+            }
+            Address::Hostname { host, port, .. } => {
+                a.set_transport(api::Address_Transport::Hostname);
+                a.set_hostname(host);
+                a.set_port(port.into());
             }
-            IpAddr::V6(ip) => {
-                a.set_transport(api::Address_Transport::TCPv6);
-                a.set_address(ip.octets().to_vec());
+            Address::WebSocket {
+                host,
+                port,
+                secure,
+                ..
+            } => {
+                a.set_transport(api::Address_Transport::WebSocket);
+                a.set_hostname(host);
+                a.set_port(port.into());
+                a.set_secure(secure);
             }
         }
-        a.set_port(self.0.port().into());
         a
     }
 }
@@ -0,0 +1,106 @@
+//! Threshold secret sharing for group signatures over batch results.
+//!
+//! `ComputationGroup` drives *when* a dealing round happens and *who*
+//! gets a share; the actual `(t, n)` secret sharing and signature
+//! combination is expressed against the small `ThresholdScheme` trait
+//! below rather than calling a pairing library directly, the same way
+//! `storage::mkvs::urkel::sync::persistent::Backend` abstracts over the
+//! embedded store it persists through. A real deployment backs this with
+//! a pairing-based VSS scheme (e.g. `threshold_crypto`); nothing in
+//! `ComputationGroup` needs to change to swap one in.
+//!
+//! This is dealer-based secret sharing, not a distributed key
+//! generation (DKG) protocol: `ThresholdScheme::deal` is a single,
+//! synchronous call in which one party -- the current committee leader,
+//! in `ComputationGroup::run_dkg` -- generates the whole group secret
+//! and every member's share of it in one step. The leader therefore
+//! does see the plaintext group secret before it ever splits it; a true
+//! DKG's threshold-trust property (no single party ever holds the full
+//! secret) does not hold here. `run_dkg`/`SubmitDkgShareRequest` keep
+//! their established names for wire compatibility, but should be read
+//! as "distribute this epoch's dealt shares", not "run a DKG round".
+use ekiden_core::bytes::{B256, H256};
+use ekiden_core::error::Result;
+
+/// `(n, t)` parameters for a committee's threshold scheme: `n` members
+/// each hold a share of the group secret, any `t` of which can combine
+/// their partial signatures into one valid group signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThresholdParams {
+    pub n: usize,
+    pub t: usize,
+}
+
+impl ThresholdParams {
+    /// Derive threshold parameters for a committee of `workers` members,
+    /// requiring one more signer than `discrepancy_tolerance` so that a
+    /// successfully combined group signature already implies no
+    /// undetected discrepancy among its signers.
+    pub fn for_committee(workers: usize, discrepancy_tolerance: usize) -> Self {
+        ThresholdParams {
+            n: workers,
+            t: ::std::cmp::min(discrepancy_tolerance + 1, workers.max(1)),
+        }
+    }
+}
+
+/// A committee epoch's group public key, opaque to `ComputationGroup`
+/// beyond being comparable and verifiable by a `ThresholdScheme`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupPublicKey(pub Vec<u8>);
+
+/// One member's secret share of the group key, opaque the same way.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SecretShare(pub Vec<u8>);
+
+/// One member's partial signature over a result digest, opaque the same
+/// way.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PartialSignature(pub Vec<u8>);
+
+/// A combined group signature, verifiable against a `GroupPublicKey`
+/// without the verifier needing any individual member's share.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GroupSignature(pub Vec<u8>);
+
+/// Pluggable `(t, n)` secret-sharing scheme: deals a group key and
+/// per-member shares, and combines `t` members' partial signatures into
+/// one group signature via Lagrange interpolation over their shares.
+pub trait ThresholdScheme: Send + Sync {
+    /// Deal a fresh `(params.t, params.n)` sharing, producing one share
+    /// per member of `members`, in the same order. This is a dealer
+    /// operation, not an interactive DKG round: the caller of `deal`
+    /// generates the full group secret itself and sees it in plaintext
+    /// before splitting it into shares. Callers that need the
+    /// threshold-trust property of a real DKG (no single party ever
+    /// holding the whole secret) need a different, multi-round
+    /// implementation of this trait.
+    fn deal(
+        &self,
+        params: ThresholdParams,
+        members: &[B256],
+    ) -> (GroupPublicKey, Vec<SecretShare>);
+
+    /// Produce this member's partial signature over `digest` using its
+    /// `share`.
+    fn sign_share(&self, share: &SecretShare, digest: H256) -> PartialSignature;
+
+    /// Combine `partials` -- indexed the same way `deal`'s `members`
+    /// were -- into one group signature. Fails if fewer than `params.t`
+    /// partials are given, or if they don't combine to a signature valid
+    /// under `group_key`.
+    fn combine(
+        &self,
+        params: ThresholdParams,
+        group_key: &GroupPublicKey,
+        digest: H256,
+        partials: &[(usize, PartialSignature)],
+    ) -> Result<GroupSignature>;
+
+    /// Reconstruct a symmetric key from `params.t` members' decrypted
+    /// shares of it (indexed consistently, though not necessarily by the
+    /// same indices `deal` used -- an output key share is tied to the
+    /// recipients it was sealed to, not to a DKG round's members). Fails
+    /// if fewer than `params.t` shares are given.
+    fn reconstruct_key(&self, params: ThresholdParams, shares: &[(usize, Vec<u8>)]) -> Result<Vec<u8>>;
+}
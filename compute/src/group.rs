@@ -1,8 +1,13 @@
 //! Computation group structures.
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use ekiden_compute_api::{ComputationGroupClient, SubmitAggCommitRequest, SubmitAggRevealRequest,
-                         SubmitBatchRequest};
+use ekiden_compute_api::{ComputationGroupClient, RequestOutputKeyShareRequest,
+                         SubmitAggCommitRequest, SubmitAggRevealRequest, SubmitBatchRequest,
+                         SubmitDkgShareRequest, SubmitOutputKeyShareRequest,
+                         SubmitValidationRequestRequest, SubmitValidationVerdictRequest,
+                         SubmitViewChangeRequest};
 use ekiden_core::bytes::{B256, B64, H256};
 use ekiden_core::environment::Environment;
 use ekiden_core::error::{Error, Result};
@@ -10,12 +15,16 @@ use ekiden_core::futures::prelude::*;
 use ekiden_core::futures::sync::mpsc;
 use ekiden_core::node::Node;
 use ekiden_core::node_group::NodeGroup;
-use ekiden_core::signature::{Signed, Signer};
+use ekiden_core::signature::{Signature, Signed, Signer};
 use ekiden_core::subscribers::StreamSubscribers;
 use ekiden_registry_base::EntityRegistryBackend;
 use ekiden_scheduler_base::{CommitteeNode, CommitteeType, Role, Scheduler};
 
 use ekiden_consensus_base::{Commitment, Reveal};
+use ekiden_contract_common::batch::EncryptedKeyShare;
+
+use dkg::{GroupPublicKey, GroupSignature, PartialSignature, SecretShare, ThresholdParams,
+          ThresholdScheme};
 
 /// Signature context used for batch submission.
 const SUBMIT_BATCH_SIGNATURE_CONTEXT: B64 = B64(*b"EkCgBaSu");
@@ -26,6 +35,127 @@ const SUBMIT_AGG_COMMIT_SIGNATURE_CONTEXT: B64 = B64(*b"EkCgACSu");
 /// Signature context used for submitting a reveal to leader for aggregation.
 const SUBMIT_AGG_REVEAL_SIGNATURE_CONTEXT: B64 = B64(*b"EkCgARSu");
 
+/// Signature context used for view-change votes.
+const VIEW_CHANGE_SIGNATURE_CONTEXT: B64 = B64(*b"EkCgVwCh");
+
+/// Whether this node can actually decrypt its own output key shares.
+/// Doing so needs an enclave unseal ecall analogous to
+/// `EnclaveContract::contract_call_batch_sealed`, which does not exist
+/// yet -- so this stays `false` and both `decrypt_own_output_key_share`
+/// and `request_output_key` fail fast and explicitly rather than
+/// pretending the feature works.
+const OUTPUT_KEY_UNSEALING_SUPPORTED: bool = false;
+
+/// Signature context used for a leader's backup-worker validation
+/// request.
+const VALIDATION_REQUEST_SIGNATURE_CONTEXT: B64 = B64(*b"EkCgVlRq");
+
+/// Signature context used for a backup worker's validation verdict.
+const VALIDATION_VERDICT_SIGNATURE_CONTEXT: B64 = B64(*b"EkCgVlVd");
+
+/// Signature context used for the leader's `AggregatedCommitments` built
+/// from commits.
+const AGG_COMMITMENTS_SIGNATURE_CONTEXT: B64 = B64(*b"EkCgAgCm");
+
+/// Signature context used for the leader's `AggregatedCommitments` built
+/// from reveals.
+const AGG_REVEALS_SIGNATURE_CONTEXT: B64 = B64(*b"EkCgAgRv");
+
+/// A single digest bucket's contributions, accumulated on the leader as
+/// workers' individual commits or reveals are opened. Kept separate from
+/// `AggregatedCommitments` itself since a bucket may yet receive more
+/// contributions before it is folded into one.
+struct AggregationBucket {
+    /// Every contributing worker's signature over this bucket's digest,
+    /// in the order they were opened.
+    signatures: Vec<Signature>,
+}
+
+impl AggregationBucket {
+    fn new() -> Self {
+        AggregationBucket {
+            signatures: Vec::new(),
+        }
+    }
+}
+
+/// Many workers' commits (or reveals) for the same batch, folded into one
+/// verifiable object. Honest workers processing the same batch commit to
+/// the same digest, so a well-formed round produces exactly one of these;
+/// a leader that has to emit more than one for the same batch is reporting
+/// a discrepancy in what the committee committed to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AggregatedCommitments {
+    /// Digest every contributing commit/reveal opened to.
+    pub digest: H256,
+    /// Public keys of the committee members who contributed to
+    /// `aggregate_sig`, in the same order as the signatures were folded.
+    pub signers: Vec<B256>,
+    /// Combination of every contributing signature over `digest`. Valid
+    /// iff each signer in `signers` actually signed `digest`.
+    pub aggregate_sig: Signature,
+}
+
+/// A leader's request for backup workers to adjudicate a disputed
+/// commitment/reveal digest, by re-executing just the batch in question
+/// rather than the whole discrepancy-resolution path re-running it on
+/// every backup unconditionally.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidationRequest {
+    pub batch_hash: H256,
+    pub candidate_digest: H256,
+    pub round: u64,
+}
+
+/// A backup worker's signed yes/no verdict on a `ValidationRequest`:
+/// whether its own re-execution of `batch_hash` agrees with
+/// `candidate_digest`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidationVerdict {
+    pub round: u64,
+    pub candidate_digest: H256,
+    pub verdict: bool,
+}
+
+/// One outstanding backup-worker validation round: the candidate digest
+/// backups were asked to confirm, and the verdicts tallied so far.
+struct ValidationRound {
+    candidate_digest: H256,
+    yes: Vec<B256>,
+    no: Vec<B256>,
+}
+
+impl ValidationRound {
+    fn new(candidate_digest: H256) -> Self {
+        ValidationRound {
+            candidate_digest,
+            yes: Vec::new(),
+            no: Vec::new(),
+        }
+    }
+}
+
+/// Fold every bucket accumulated in `pool` into an `AggregatedCommitments`
+/// per non-empty digest, clearing the pool as it goes.
+fn drain_aggregation_pool(
+    pool: &Mutex<HashMap<H256, AggregationBucket>>,
+) -> Vec<AggregatedCommitments> {
+    pool.lock()
+        .unwrap()
+        .drain()
+        .filter(|(_, bucket)| !bucket.signatures.is_empty())
+        .map(|(digest, bucket)| AggregatedCommitments {
+            digest,
+            signers: bucket
+                .signatures
+                .iter()
+                .map(|signature| signature.public_key)
+                .collect(),
+            aggregate_sig: Signature::aggregate(&bucket.signatures),
+        })
+        .collect()
+}
+
 /// Commands for communicating with the computation group from other tasks.
 enum Command {
     /// Submit batch to workers.
@@ -36,6 +166,15 @@ enum Command {
     SubmitAggCommit(Commitment),
     /// Submit a reveal to the leader for aggregation.
     SubmitAggReveal(Reveal),
+    /// A vote, from ourselves or another committee member, to move to a
+    /// new view because the current aggregation leader appears stalled.
+    ViewChange(Signed<u64>),
+    /// Dispatch a backup-worker validation request for a disputed digest
+    /// found while opening a commit or reveal: `(batch_hash,
+    /// candidate_digest)`.
+    RequestValidation(H256, H256),
+    /// Submit our own validation verdict to the leader.
+    SubmitValidationVerdict(ValidationVerdict),
 }
 
 struct Inner {
@@ -61,6 +200,70 @@ struct Inner {
     command_receiver: Mutex<Option<mpsc::UnboundedReceiver<Command>>>,
     /// Role subscribers.
     role_subscribers: StreamSubscribers<Option<Role>>,
+    /// Commits opened via `open_agg_commit`, bucketed by digest, waiting
+    /// to be folded into an `AggregatedCommitments`. Only ever populated
+    /// while we are the leader.
+    agg_commit_pool: Mutex<HashMap<H256, AggregationBucket>>,
+    /// Same as `agg_commit_pool`, for reveals opened via `open_agg_reveal`.
+    agg_reveal_pool: Mutex<HashMap<H256, AggregationBucket>>,
+    /// Current aggregation view. View 0 keeps the scheduler-assigned
+    /// `Role::Leader`; the leader for any later view `v` is
+    /// deterministically `committee[v % workers]` among
+    /// `Role::Worker`/`Role::Leader` members. Bumped by a successful
+    /// view-change quorum and reset on every committee update.
+    view: Mutex<u64>,
+    /// Votes collected for candidate views not yet adopted, keyed by view
+    /// number. A view is dropped from here once it is adopted, or once a
+    /// higher view is.
+    view_change_votes: Mutex<HashMap<u64, Vec<B256>>>,
+    /// Wall-clock time of the last observed aggregation progress (a
+    /// committee update, or an explicit `notify_progress` call). Compared
+    /// against `view_change_timeout` to decide whether the current view's
+    /// leader has stalled.
+    last_progress: Mutex<Instant>,
+    /// How long to go without progress before proposing a view change.
+    view_change_timeout: Duration,
+    /// Pairing/secret-sharing implementation backing the per-epoch DKG
+    /// round. See `dkg::ThresholdScheme`.
+    threshold_scheme: Arc<ThresholdScheme>,
+    /// Number of committee members a group signature must tolerate being
+    /// faulty or unresponsive, used to derive `ThresholdParams` for each
+    /// epoch's DKG round.
+    discrepancy_tolerance: usize,
+    /// Group public key from the current epoch's completed DKG round, if
+    /// any. `None` before the round completes, or if it aborted because
+    /// fewer than `t` members took part -- in which case aggregation
+    /// falls back to the per-worker `SubmitAggCommit`/`SubmitAggReveal`
+    /// path instead of a group signature.
+    group_key: Mutex<Option<GroupPublicKey>>,
+    /// This node's own share of the current epoch's group secret, if a
+    /// DKG round has completed and we were dealt one.
+    my_share: Mutex<Option<SecretShare>>,
+    /// Group public key subscribers, notified once per completed (or
+    /// aborted) DKG round.
+    group_key_subscribers: StreamSubscribers<Option<GroupPublicKey>>,
+    /// Batch hash of the batch currently being committed/revealed on, set
+    /// by `handle_submit`. Used as the `batch_hash` in a validation
+    /// request, since a validation round only ever concerns the batch the
+    /// leader most recently submitted.
+    current_batch: Mutex<Option<H256>>,
+    /// Distinct digests seen via `open_agg_commit` for `current_batch`,
+    /// used only to detect a discrepancy (more than one distinct digest)
+    /// and trigger a validation round. Cleared whenever `handle_submit`
+    /// starts a new batch.
+    seen_commit_digests: Mutex<HashSet<H256>>,
+    /// Same as `seen_commit_digests`, for `open_agg_reveal`.
+    seen_reveal_digests: Mutex<HashSet<H256>>,
+    /// Outstanding backup-worker validation rounds, keyed by round
+    /// number.
+    validation_rounds: Mutex<HashMap<u64, ValidationRound>>,
+    /// Next round number to hand out for a validation request.
+    next_validation_round: Mutex<u64>,
+    /// Encrypted output key shares, sealed by `contract_call_batch_sealed`
+    /// to each recipient, kept by the node that distributed them so it
+    /// can re-send to a recipient that missed the original broadcast.
+    /// Keyed by batch hash, then recipient public key.
+    output_key_shares: Mutex<HashMap<H256, HashMap<B256, EncryptedKeyShare>>>,
 }
 
 impl Inner {
@@ -90,6 +293,9 @@ impl ComputationGroup {
         entity_registry: Arc<EntityRegistryBackend>,
         signer: Arc<Signer>,
         environment: Arc<Environment>,
+        view_change_timeout: Duration,
+        threshold_scheme: Arc<ThresholdScheme>,
+        discrepancy_tolerance: usize,
     ) -> Self {
         let (command_sender, command_receiver) = mpsc::unbounded();
 
@@ -106,6 +312,23 @@ impl ComputationGroup {
                 command_sender,
                 command_receiver: Mutex::new(Some(command_receiver)),
                 role_subscribers: StreamSubscribers::new(),
+                agg_commit_pool: Mutex::new(HashMap::new()),
+                agg_reveal_pool: Mutex::new(HashMap::new()),
+                view: Mutex::new(0),
+                view_change_votes: Mutex::new(HashMap::new()),
+                last_progress: Mutex::new(Instant::now()),
+                view_change_timeout,
+                threshold_scheme,
+                discrepancy_tolerance,
+                group_key: Mutex::new(None),
+                my_share: Mutex::new(None),
+                group_key_subscribers: StreamSubscribers::new(),
+                current_batch: Mutex::new(None),
+                seen_commit_digests: Mutex::new(HashSet::new()),
+                seen_reveal_digests: Mutex::new(HashSet::new()),
+                validation_rounds: Mutex::new(HashMap::new()),
+                next_validation_round: Mutex::new(0),
+                output_key_shares: Mutex::new(HashMap::new()),
             }),
         };
         instance.start();
@@ -164,6 +387,15 @@ impl ComputationGroup {
                     Command::SubmitAggReveal(reveal) => {
                         Self::handle_submit_agg_reveal(inner.clone(), reveal)
                     }
+                    Command::ViewChange(signed_view) => {
+                        Self::handle_view_change(inner.clone(), signed_view)
+                    }
+                    Command::RequestValidation(batch_hash, candidate_digest) => {
+                        Self::handle_request_validation(inner.clone(), batch_hash, candidate_digest)
+                    }
+                    Command::SubmitValidationVerdict(verdict) => {
+                        Self::handle_submit_validation_verdict(inner.clone(), verdict)
+                    }
                 },
             )
         });
@@ -188,6 +420,18 @@ impl ComputationGroup {
         // Clear the current leader as well.
         *inner.leader.lock().unwrap() = None;
 
+        // A new committee starts a fresh round of view changes.
+        *inner.view.lock().unwrap() = 0;
+        inner.view_change_votes.lock().unwrap().clear();
+        *inner.last_progress.lock().unwrap() = Instant::now();
+
+        // The previous epoch's group key no longer corresponds to this
+        // committee's membership; a new DKG round is needed before group
+        // signatures can be produced again.
+        *inner.group_key.lock().unwrap() = None;
+        *inner.my_share.lock().unwrap() = None;
+        inner.group_key_subscribers.notify(&None);
+
         // Check if we are still part of the committee. If we are not, do not populate the node
         // group with any nodes as it is not needed.
         if !members
@@ -248,6 +492,15 @@ impl ComputationGroup {
                         inner.role_subscribers.notify(&new_role);
                     }
 
+                    // Run a fresh DKG round for this epoch's committee. Only
+                    // the leader deals; other members just wait to be sent
+                    // their share, which arrives via `accept_dkg_share`.
+                    if inner.leader.lock().unwrap().as_ref().map(|node| node.public_key)
+                        == Some(inner.signer.get_public_key())
+                    {
+                        Self::run_dkg(inner.clone());
+                    }
+
                     info!("Update of computation group committee finished");
 
                     Ok(())
@@ -266,6 +519,11 @@ impl ComputationGroup {
     fn handle_submit(inner: Arc<Inner>, batch_hash: H256) -> BoxFuture<()> {
         trace!("Submitting batch to workers");
 
+        // A new batch starts a fresh round of discrepancy detection.
+        *inner.current_batch.lock().unwrap() = Some(batch_hash);
+        inner.seen_commit_digests.lock().unwrap().clear();
+        inner.seen_reveal_digests.lock().unwrap().clear();
+
         // Sign batch.
         let signed_batch = Signed::sign(&inner.signer, &SUBMIT_BATCH_SIGNATURE_CONTEXT, batch_hash);
 
@@ -370,6 +628,607 @@ impl ComputationGroup {
             .into_box()
     }
 
+    /// Handle an incoming view-change vote, from ourselves or another
+    /// committee member.
+    fn handle_view_change(inner: Arc<Inner>, signed_view: Signed<u64>) -> BoxFuture<()> {
+        let signer = signed_view.signature.public_key;
+
+        let view = match signed_view.open(&VIEW_CHANGE_SIGNATURE_CONTEXT) {
+            Ok(view) => view,
+            Err(error) => {
+                warn!("Dropping view change with invalid signature: {}", error.message);
+                return future::ok(()).into_box();
+            }
+        };
+
+        let is_committee_member = inner
+            .committee
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|node| node.public_key == signer);
+        if !is_committee_member {
+            warn!("Dropping view change not signed by a committee member");
+            return future::ok(()).into_box();
+        }
+
+        Self::record_view_change_vote(&inner, view, signer);
+
+        future::ok(()).into_box()
+    }
+
+    /// As the leader, dispatch a validation request for `candidate_digest`
+    /// of `batch_hash` to every backup worker, and open a round to tally
+    /// their verdicts.
+    fn handle_request_validation(
+        inner: Arc<Inner>,
+        batch_hash: H256,
+        candidate_digest: H256,
+    ) -> BoxFuture<()> {
+        let round = {
+            let mut next_round = inner.next_validation_round.lock().unwrap();
+            let round = *next_round;
+            *next_round += 1;
+            round
+        };
+
+        inner
+            .validation_rounds
+            .lock()
+            .unwrap()
+            .insert(round, ValidationRound::new(candidate_digest));
+
+        let request_body = ValidationRequest {
+            batch_hash,
+            candidate_digest,
+            round,
+        };
+        let signed_request =
+            Signed::sign(&inner.signer, &VALIDATION_REQUEST_SIGNATURE_CONTEXT, request_body);
+
+        let mut request = SubmitValidationRequestRequest::new();
+        request.set_batch_hash(batch_hash.to_vec());
+        request.set_candidate_digest(candidate_digest.to_vec());
+        request.set_round(round);
+        request.set_signature(signed_request.signature.into());
+
+        inner
+            .node_group
+            .call_filtered(
+                |_, node| node.role == Role::BackupWorker,
+                move |client, _| client.submit_validation_request_async(&request),
+            )
+            .and_then(|results| {
+                for result in results {
+                    if let Err(error) = result {
+                        error!("Failed to dispatch validation request: {}", error.message);
+                    }
+                }
+
+                Ok(())
+            })
+            .into_box()
+    }
+
+    /// As a backup worker, sign and send our validation verdict to the
+    /// leader.
+    fn handle_submit_validation_verdict(inner: Arc<Inner>, verdict: ValidationVerdict) -> BoxFuture<()> {
+        trace!("Submitting validation verdict to leader");
+
+        let signed_verdict = Signed::sign(&inner.signer, &VALIDATION_VERDICT_SIGNATURE_CONTEXT, verdict.clone());
+
+        let mut request = SubmitValidationVerdictRequest::new();
+        request.set_round(verdict.round);
+        request.set_candidate_digest(verdict.candidate_digest.to_vec());
+        request.set_verdict(verdict.verdict);
+        request.set_signature(signed_verdict.signature.into());
+
+        inner
+            .node_group
+            .call_filtered(
+                |_, node| node.role == Role::Leader,
+                move |client, _| client.submit_validation_verdict_async(&request),
+            )
+            .and_then(|results| {
+                for result in results {
+                    if let Err(error) = result {
+                        error!("Failed to submit validation verdict: {}", error.message);
+                    }
+                }
+
+                Ok(())
+            })
+            .into_box()
+    }
+
+    /// Submit our own (backup worker's) validation verdict to the leader.
+    ///
+    /// Returns the current leader of the computation group.
+    pub fn submit_validation_verdict(
+        &self,
+        round: u64,
+        candidate_digest: H256,
+        verdict: bool,
+    ) -> CommitteeNode {
+        self.inner
+            .command_sender
+            .unbounded_send(Command::SubmitValidationVerdict(ValidationVerdict {
+                round,
+                candidate_digest,
+                verdict,
+            }))
+            .unwrap();
+
+        self.inner.leader.lock().unwrap().clone().unwrap()
+    }
+
+    /// Open a backup worker's validation verdict, verifying it came from
+    /// a committee member with the `Role::BackupWorker` role and that we
+    /// are the current leader, then tally it toward the round's outcome.
+    /// Returns the round's resolved outcome -- `(candidate_digest,
+    /// majority_agrees, signers)`, where `signers` is the public keys of
+    /// the backup workers whose verdict matched `majority_agrees`, kept
+    /// for auditability -- once a majority of backup workers have cast the
+    /// same verdict. Returns `Ok(None)` until then, including when the
+    /// votes are split with no side reaching that majority.
+    pub fn open_validation_verdict(
+        &self,
+        signed_verdict: Signed<ValidationVerdict>,
+    ) -> Result<Option<(H256, bool, Vec<B256>)>> {
+        let leader = self.inner.leader.lock().unwrap().clone().unwrap();
+        if leader.public_key != self.inner.signer.get_public_key() {
+            return Err(Error::new("am not the current compute committee leader"));
+        }
+
+        let signer = signed_verdict.signature.public_key;
+        let backup_workers = {
+            let committee = self.inner.committee.lock().unwrap();
+            if !committee
+                .iter()
+                .any(|node| node.role == Role::BackupWorker && node.public_key == signer)
+            {
+                return Err(Error::new("not signed by a compute committee backup worker"));
+            }
+
+            committee
+                .iter()
+                .filter(|node| node.role == Role::BackupWorker)
+                .count()
+        };
+
+        let verdict = signed_verdict.open(&VALIDATION_VERDICT_SIGNATURE_CONTEXT)?;
+
+        let mut rounds = self.inner.validation_rounds.lock().unwrap();
+        let round = rounds
+            .get_mut(&verdict.round)
+            .ok_or_else(|| Error::new("unknown validation round"))?;
+
+        if round.candidate_digest != verdict.candidate_digest {
+            return Err(Error::new("verdict for a stale validation round candidate"));
+        }
+
+        if verdict.verdict {
+            if !round.yes.contains(&signer) {
+                round.yes.push(signer);
+            }
+        } else if !round.no.contains(&signer) {
+            round.no.push(signer);
+        }
+
+        let quorum = backup_workers / 2 + 1;
+        if round.yes.len() >= quorum {
+            let digest = round.candidate_digest;
+            let round = rounds.remove(&verdict.round).unwrap();
+            return Ok(Some((digest, true, round.yes)));
+        }
+        if round.no.len() >= quorum {
+            let digest = round.candidate_digest;
+            let round = rounds.remove(&verdict.round).unwrap();
+            return Ok(Some((digest, false, round.no)));
+        }
+
+        Ok(None)
+    }
+
+    /// Determine the deterministic aggregation leader for `view` within
+    /// `committee`, scoped to the `Role::Worker`/`Role::Leader` members
+    /// (backup workers never aggregate, so they never lead either).
+    fn leader_for_view(committee: &[CommitteeNode], view: u64) -> Option<CommitteeNode> {
+        let eligible: Vec<&CommitteeNode> = committee
+            .iter()
+            .filter(|node| node.role == Role::Worker || node.role == Role::Leader)
+            .collect();
+
+        if eligible.is_empty() {
+            return None;
+        }
+
+        Some(eligible[(view % eligible.len() as u64) as usize].clone())
+    }
+
+    /// Record `signer`'s vote for `view`, adopting it -- and recomputing
+    /// the leader -- once a quorum of `get_number_of_workers() / 2 + 1` is
+    /// reached. Votes at or below the currently adopted view are ignored
+    /// as stale.
+    fn record_view_change_vote(inner: &Arc<Inner>, view: u64, signer: B256) {
+        if view <= *inner.view.lock().unwrap() {
+            return;
+        }
+
+        let quorum = {
+            let committee = inner.committee.lock().unwrap();
+            committee
+                .iter()
+                .filter(|node| node.role == Role::Worker || node.role == Role::Leader)
+                .count() / 2 + 1
+        };
+
+        let adopt = {
+            let mut votes = inner.view_change_votes.lock().unwrap();
+            let voters = votes.entry(view).or_insert_with(Vec::new);
+            if !voters.contains(&signer) {
+                voters.push(signer);
+            }
+            voters.len() >= quorum
+        };
+
+        if !adopt {
+            return;
+        }
+
+        let mut current_view = inner.view.lock().unwrap();
+        if view <= *current_view {
+            return;
+        }
+        *current_view = view;
+        drop(current_view);
+
+        inner.view_change_votes.lock().unwrap().retain(|&v, _| v > view);
+
+        let new_leader = {
+            let committee = inner.committee.lock().unwrap();
+            Self::leader_for_view(&committee, view)
+        };
+
+        info!(
+            "View change to view {} adopted, recomputed aggregation leader",
+            view
+        );
+        *inner.leader.lock().unwrap() = new_leader;
+        *inner.last_progress.lock().unwrap() = Instant::now();
+
+        inner.role_subscribers.notify(&inner.get_role());
+    }
+
+    /// Broadcast a proposal to move to `view` to the whole committee, and
+    /// record our own vote locally so that a lone node observing the
+    /// stall still counts toward quorum.
+    fn initiate_view_change(inner: &Arc<Inner>, view: u64) {
+        info!(
+            "Proposing view change to view {} (aggregation leader appears stalled)",
+            view
+        );
+
+        let signed_view = Signed::sign(&inner.signer, &VIEW_CHANGE_SIGNATURE_CONTEXT, view);
+        let signer = signed_view.signature.public_key;
+
+        let mut request = SubmitViewChangeRequest::new();
+        request.set_view(view);
+        request.set_signature(signed_view.signature.into());
+
+        inner.environment.spawn(
+            inner
+                .node_group
+                .call_filtered(|_, _| true, move |client, _| {
+                    client.submit_view_change_async(&request)
+                })
+                .and_then(|results| {
+                    for result in results {
+                        if let Err(error) = result {
+                            error!("Failed to broadcast view change: {}", error.message);
+                        }
+                    }
+
+                    Ok(())
+                })
+                .into_box(),
+        );
+
+        Self::record_view_change_vote(inner, view, signer);
+    }
+
+    /// If no aggregation progress has been observed within
+    /// `view_change_timeout`, propose moving to the next view rather than
+    /// keep routing submissions to a leader that may have stalled.
+    fn check_leader_stall(&self) {
+        let stalled = self.inner.last_progress.lock().unwrap().elapsed() >= self.inner.view_change_timeout;
+        if !stalled {
+            return;
+        }
+
+        let next_view = *self.inner.view.lock().unwrap() + 1;
+        Self::initiate_view_change(&self.inner, next_view);
+    }
+
+    /// Record that aggregation is making progress under the current
+    /// leader (e.g. a leader-signed batch or a finalized result was
+    /// observed), resetting the view-change stall timer.
+    pub fn notify_progress(&self) {
+        *self.inner.last_progress.lock().unwrap() = Instant::now();
+    }
+
+    /// As the current leader, deal a fresh `(t, n)` sharing for this
+    /// epoch's committee and send each member its own share over the
+    /// existing computation group channels. Aborts -- leaving
+    /// `group_key` at `None`, so result signing falls back to the
+    /// per-worker `SubmitAggCommit`/`SubmitAggReveal` path -- if there
+    /// aren't at least `t` eligible members to deal to.
+    ///
+    /// Despite the name, this is dealer-based secret sharing, not an
+    /// interactive DKG round: as the dealer, this node generates the
+    /// whole group secret in `ThresholdScheme::deal` and sees it in
+    /// plaintext before splitting it into shares (see `dkg`'s module
+    /// docs). The name is kept for consistency with
+    /// `SubmitDkgShareRequest`/`accept_dkg_share`.
+    fn run_dkg(inner: Arc<Inner>) {
+        let members: Vec<B256> = {
+            let committee = inner.committee.lock().unwrap();
+            committee
+                .iter()
+                .filter(|node| node.role == Role::Worker || node.role == Role::Leader)
+                .map(|node| node.public_key)
+                .collect()
+        };
+
+        let params = ThresholdParams::for_committee(members.len(), inner.discrepancy_tolerance);
+        if members.len() < params.t {
+            warn!(
+                "Not enough committee members ({}) to meet DKG threshold {}, skipping DKG round",
+                members.len(),
+                params.t
+            );
+            return;
+        }
+
+        let (group_key, shares) = inner.threshold_scheme.deal(params, &members);
+
+        for (member, share) in members.iter().zip(shares.into_iter()) {
+            if *member == inner.signer.get_public_key() {
+                *inner.my_share.lock().unwrap() = Some(share);
+                continue;
+            }
+
+            // TODO: shares must be encrypted to each member's key before
+            // being sent over the wire; the transport-level encryption
+            // this relies on is out of scope here.
+            let mut request = SubmitDkgShareRequest::new();
+            request.set_group_key(group_key.0.clone());
+            request.set_share(share.0);
+
+            let member = *member;
+            inner.environment.spawn(
+                inner
+                    .node_group
+                    .call_filtered(
+                        move |_, node| node.public_key == member,
+                        move |client, _| client.submit_dkg_share_async(&request),
+                    )
+                    .and_then(move |results| {
+                        for result in results {
+                            if let Err(error) = result {
+                                error!("Failed to deliver DKG share to {:?}: {}", member, error.message);
+                            }
+                        }
+
+                        Ok(())
+                    })
+                    .into_box(),
+            );
+        }
+
+        *inner.group_key.lock().unwrap() = Some(group_key.clone());
+        inner.group_key_subscribers.notify(&Some(group_key));
+    }
+
+    /// Accept a share dealt to us by the leader for this epoch's DKG
+    /// round. Called by the gRPC handler when it receives a
+    /// `SubmitDkgShareRequest`, mirroring how `open_agg_commit` is called
+    /// for an incoming `SubmitAggCommitRequest`.
+    pub fn accept_dkg_share(&self, group_key: GroupPublicKey, share: SecretShare) {
+        *self.inner.my_share.lock().unwrap() = Some(share);
+        *self.inner.group_key.lock().unwrap() = Some(group_key.clone());
+        self.inner.group_key_subscribers.notify(&Some(group_key));
+    }
+
+    /// Subscribe to the computation group's current DKG group public
+    /// key. `None` until the epoch's DKG round completes, and again
+    /// whenever the committee is rotated and a new round starts.
+    pub fn watch_group_key(&self) -> BoxStream<Option<GroupPublicKey>> {
+        self.inner.group_key_subscribers.subscribe().1
+    }
+
+    /// Distribute a batch's sealed output key shares (as returned by
+    /// `EnclaveContract::contract_call_batch_sealed`) to their recipients
+    /// over the existing computation group channels, so each recipient
+    /// can later answer a `request_output_key` call with its own
+    /// decrypted share.
+    pub fn distribute_output_key_shares(&self, batch_hash: H256, shares: Vec<(B256, EncryptedKeyShare)>) {
+        self.inner
+            .output_key_shares
+            .lock()
+            .unwrap()
+            .insert(batch_hash, shares.iter().cloned().collect());
+
+        for (recipient, share) in shares {
+            if recipient == self.inner.signer.get_public_key() {
+                // We are a recipient ourselves; nothing to send over the
+                // wire for our own share.
+                continue;
+            }
+
+            let mut request = SubmitOutputKeyShareRequest::new();
+            request.set_batch_hash(batch_hash.to_vec());
+            request.set_share(share.into());
+
+            self.inner.environment.spawn(
+                self.inner
+                    .node_group
+                    .call_filtered(
+                        move |_, node| node.public_key == recipient,
+                        move |client, _| client.submit_output_key_share_async(&request),
+                    )
+                    .and_then(move |results| {
+                        for result in results {
+                            if let Err(error) = result {
+                                error!(
+                                    "Failed to deliver output key share to {:?}: {}",
+                                    recipient, error.message
+                                );
+                            }
+                        }
+
+                        Ok(())
+                    })
+                    .into_box(),
+            );
+        }
+    }
+
+    /// Accept an output key share dealt to us for `batch_hash`, as the
+    /// gRPC handler does for an incoming `SubmitOutputKeyShareRequest`.
+    /// The share stays sealed until an actual `request_output_key` call
+    /// asks us to decrypt and contribute it.
+    pub fn accept_output_key_share(&self, batch_hash: H256, share: EncryptedKeyShare) {
+        self.inner
+            .output_key_shares
+            .lock()
+            .unwrap()
+            .entry(batch_hash)
+            .or_insert_with(HashMap::new)
+            .insert(self.inner.signer.get_public_key(), share);
+    }
+
+    /// As a recipient, decrypt our own share of `batch_hash`'s output
+    /// key for a `request_output_key_share` RPC. Returns `Ok(None)` if we
+    /// were never dealt a share for this batch; fails rather than
+    /// returning the share if it cannot actually be decrypted.
+    pub fn decrypt_own_output_key_share(&self, batch_hash: H256) -> Result<Option<Vec<u8>>> {
+        let have_share = self
+            .inner
+            .output_key_shares
+            .lock()
+            .unwrap()
+            .get(&batch_hash)
+            .map_or(false, |shares| shares.contains_key(&self.inner.signer.get_public_key()));
+        if !have_share {
+            return Ok(None);
+        }
+
+        // Unsealing requires an enclave ecall the way
+        // `contract_call_batch_sealed` handles batch outputs, which isn't
+        // wired up here; fail loudly instead of handing the caller back
+        // the still-sealed bytes as if they were the decrypted share.
+        debug_assert!(!OUTPUT_KEY_UNSEALING_SUPPORTED);
+        Err(Error::new("output key share decryption is not implemented"))
+    }
+
+    /// Collect at least `t` committee members' decrypted output key
+    /// shares for `batch_hash` over the existing client channels, and
+    /// reconstruct the output key once the threshold is met -- so
+    /// reading a batch's results requires committee cooperation, not a
+    /// single node's key.
+    pub fn request_output_key(&self, batch_hash: H256) -> BoxFuture<Vec<u8>> {
+        if !OUTPUT_KEY_UNSEALING_SUPPORTED {
+            // Every recipient's `decrypt_own_output_key_share` fails by
+            // construction, so running the full committee round below
+            // would always end in "not enough committee members
+            // responded" -- a liveness-sounding error that hides the
+            // real cause. Surface the actual, unsupported-feature error
+            // directly instead.
+            return future::err(Error::new(
+                "output key reconstruction is not supported: output key share decryption is not implemented",
+            )).into_box();
+        }
+
+        let inner = self.inner.clone();
+        let recipients: Vec<B256> = {
+            let output_key_shares = inner.output_key_shares.lock().unwrap();
+            match output_key_shares.get(&batch_hash) {
+                Some(shares) => shares.keys().cloned().collect(),
+                None => {
+                    return future::err(Error::new("no output key shares known for this batch"))
+                        .into_box()
+                }
+            }
+        };
+
+        let params = ThresholdParams::for_committee(recipients.len(), inner.discrepancy_tolerance);
+
+        let mut request = RequestOutputKeyShareRequest::new();
+        request.set_batch_hash(batch_hash.to_vec());
+
+        inner
+            .node_group
+            .call_filtered(
+                move |_, node| recipients.contains(&node.public_key),
+                move |client, _| client.request_output_key_share_async(&request),
+            )
+            .and_then(move |results| {
+                let shares: Vec<(usize, Vec<u8>)> = results
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(index, result)| {
+                        result.ok().map(|response| (index, response.get_share().to_vec()))
+                    })
+                    .collect();
+
+                if shares.len() < params.t {
+                    return Err(Error::new(
+                        "not enough committee members responded to reconstruct the output key",
+                    ));
+                }
+
+                inner.threshold_scheme.reconstruct_key(params, &shares)
+            })
+            .into_box()
+    }
+
+    /// Produce this node's partial signature over a batch result digest,
+    /// if a DKG round has completed and we hold a share. Returns `None`
+    /// if there is no group key yet, in which case the caller should fall
+    /// back to `submit_agg_commit`/`submit_agg_reveal`.
+    pub fn sign_result(&self, digest: H256) -> Option<PartialSignature> {
+        let share = self.inner.my_share.lock().unwrap();
+        share
+            .as_ref()
+            .map(|share| self.inner.threshold_scheme.sign_share(share, digest))
+    }
+
+    /// As the leader, combine committee members' partial signatures over
+    /// `digest` into one group signature verifiable against the current
+    /// `group_key`. Fails if there is no group key yet, or fewer than `t`
+    /// partials were given -- in both cases the caller should fall back
+    /// to the per-worker aggregation path instead.
+    pub fn combine_result_signature(
+        &self,
+        digest: H256,
+        partials: &[(usize, PartialSignature)],
+    ) -> Result<GroupSignature> {
+        let group_key = self.inner
+            .group_key
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| Error::new("no group key for the current epoch"))?;
+
+        let workers = self.get_number_of_workers();
+        let params = ThresholdParams::for_committee(workers, self.inner.discrepancy_tolerance);
+
+        self.inner
+            .threshold_scheme
+            .combine(params, &group_key, digest, partials)
+    }
+
     /// Submit batch to workers in the computation group.
     pub fn submit(&self, batch_hash: H256) -> Vec<CommitteeNode> {
         self.inner
@@ -385,6 +1244,8 @@ impl ComputationGroup {
     ///
     /// Returns the current leader of the computation group.
     pub fn submit_agg_commit(&self, commit: Commitment) -> CommitteeNode {
+        self.check_leader_stall();
+
         self.inner
             .command_sender
             .unbounded_send(Command::SubmitAggCommit(commit))
@@ -397,6 +1258,8 @@ impl ComputationGroup {
     ///
     /// Returns the current leader of the computation group.
     pub fn submit_agg_reveal(&self, reveal: Reveal) -> CommitteeNode {
+        self.check_leader_stall();
+
         self.inner
             .command_sender
             .unbounded_send(Command::SubmitAggReveal(reveal))
@@ -461,10 +1324,39 @@ impl ComputationGroup {
             return Err(Error::new("not signed by compute committee worker"));
         }
 
-        Ok((
-            signed_commit.open(&SUBMIT_AGG_COMMIT_SIGNATURE_CONTEXT)?,
-            role,
-        ))
+        let commitment = signed_commit.open(&SUBMIT_AGG_COMMIT_SIGNATURE_CONTEXT)?;
+        self.check_digest_discrepancy(&self.inner.seen_commit_digests, commitment.get_digest());
+
+        Ok((commitment, role))
+    }
+
+    /// Record `digest` as seen for the batch currently being committed
+    /// to (or revealed on), dispatching a backup-worker validation
+    /// request the moment a second, distinct digest shows up.
+    fn check_digest_discrepancy(&self, seen: &Mutex<HashSet<H256>>, digest: H256) {
+        let is_new = seen.lock().unwrap().insert(digest);
+        if !is_new {
+            return;
+        }
+
+        let distinct = seen.lock().unwrap().len();
+        if distinct <= 1 {
+            return;
+        }
+
+        let batch_hash = match *self.inner.current_batch.lock().unwrap() {
+            Some(batch_hash) => batch_hash,
+            None => return,
+        };
+
+        warn!(
+            "Detected discrepancy in opened digests for batch {:?}, requesting backup validation",
+            batch_hash
+        );
+        self.inner
+            .command_sender
+            .unbounded_send(Command::RequestValidation(batch_hash, digest))
+            .unwrap();
     }
 
     pub fn open_agg_reveal(&self, signed_reveal: Signed<Reveal>) -> Result<(Reveal, Role)> {
@@ -500,10 +1392,133 @@ impl ComputationGroup {
             return Err(Error::new("not signed by compute committee worker"));
         }
 
-        Ok((
-            signed_reveal.open(&SUBMIT_AGG_REVEAL_SIGNATURE_CONTEXT)?,
-            role,
-        ))
+        let reveal = signed_reveal.open(&SUBMIT_AGG_REVEAL_SIGNATURE_CONTEXT)?;
+        self.check_digest_discrepancy(&self.inner.seen_reveal_digests, reveal.get_digest());
+
+        Ok((reveal, role))
+    }
+
+    /// Open a commit exactly as `open_agg_commit` does, additionally
+    /// bucketing it by digest so it can later be folded into an
+    /// `AggregatedCommitments` by `build_agg_commitments`. Kept as a
+    /// separate entry point so callers that only need per-item
+    /// verification can keep using `open_agg_commit` without paying for
+    /// bucket bookkeeping they won't use.
+    pub fn aggregate_commit(&self, signed_commit: Signed<Commitment>) -> Result<Role> {
+        let signature = signed_commit.signature.clone();
+        let (commitment, role) = self.open_agg_commit(signed_commit)?;
+
+        let mut pool = self.inner.agg_commit_pool.lock().unwrap();
+        pool.entry(commitment.get_digest())
+            .or_insert_with(AggregationBucket::new)
+            .signatures
+            .push(signature);
+
+        Ok(role)
+    }
+
+    /// Open a reveal exactly as `open_agg_reveal` does, additionally
+    /// bucketing it by digest so it can later be folded into an
+    /// `AggregatedCommitments` by `build_agg_reveals`.
+    pub fn aggregate_reveal(&self, signed_reveal: Signed<Reveal>) -> Result<Role> {
+        let signature = signed_reveal.signature.clone();
+        let (reveal, role) = self.open_agg_reveal(signed_reveal)?;
+
+        let mut pool = self.inner.agg_reveal_pool.lock().unwrap();
+        pool.entry(reveal.get_digest())
+            .or_insert_with(AggregationBucket::new)
+            .signatures
+            .push(signature);
+
+        Ok(role)
+    }
+
+    /// Fold every commit bucketed by `aggregate_commit` since the last
+    /// call into one signed `AggregatedCommitments` per distinct digest,
+    /// and clear the pool. More than one returned item means the
+    /// committee disagreed about the digest for this batch.
+    pub fn build_agg_commitments(&self) -> Vec<Signed<AggregatedCommitments>> {
+        drain_aggregation_pool(&self.inner.agg_commit_pool)
+            .into_iter()
+            .map(|agg| Signed::sign(&self.inner.signer, &AGG_COMMITMENTS_SIGNATURE_CONTEXT, agg))
+            .collect()
+    }
+
+    /// Same as `build_agg_commitments`, for reveals bucketed by
+    /// `aggregate_reveal`.
+    pub fn build_agg_reveals(&self) -> Vec<Signed<AggregatedCommitments>> {
+        drain_aggregation_pool(&self.inner.agg_reveal_pool)
+            .into_iter()
+            .map(|agg| Signed::sign(&self.inner.signer, &AGG_REVEALS_SIGNATURE_CONTEXT, agg))
+            .collect()
+    }
+
+    /// Verify that given aggregated commitments were built and signed by
+    /// the current leader, mirroring `open_remote_batch`. Returns the
+    /// digest they cover and the roles of every committee member whose
+    /// signature was folded into `aggregate_sig`, so the caller can check
+    /// that enough of the committee is represented without having to
+    /// verify each contributing signature individually.
+    pub fn open_agg_commitments(
+        &self,
+        signed_agg: Signed<AggregatedCommitments>,
+    ) -> Result<(H256, Vec<Role>)> {
+        self.open_agg_aggregate(signed_agg, &AGG_COMMITMENTS_SIGNATURE_CONTEXT)
+    }
+
+    /// Same as `open_agg_commitments`, for an aggregate built from
+    /// reveals.
+    pub fn open_agg_reveals(
+        &self,
+        signed_agg: Signed<AggregatedCommitments>,
+    ) -> Result<(H256, Vec<Role>)> {
+        self.open_agg_aggregate(signed_agg, &AGG_REVEALS_SIGNATURE_CONTEXT)
+    }
+
+    fn open_agg_aggregate(
+        &self,
+        signed_agg: Signed<AggregatedCommitments>,
+        context: &'static B64,
+    ) -> Result<(H256, Vec<Role>)> {
+        let committee = {
+            let committee = self.inner.committee.lock().unwrap();
+            if !committee.iter().any(|node| {
+                node.role == Role::Leader && node.public_key == signed_agg.signature.public_key
+            }) {
+                warn!("Dropping aggregated commitments not signed by compute committee leader");
+                return Err(Error::new("not signed by compute committee leader"));
+            }
+
+            committee.clone()
+        };
+
+        let agg = signed_agg.open(context)?;
+
+        // The leader's own wrapper signature only proves the leader
+        // relayed this `AggregatedCommitments` honestly, not that
+        // `aggregate_sig` is actually a valid combination of `signers`'
+        // signatures over `digest` -- a leader bug (or a compromised
+        // leader) could otherwise hand out fabricated `signers` with
+        // arbitrary `aggregate_sig` bytes and have it accepted on the
+        // strength of the wrapper signature alone. Check the aggregate
+        // itself before trusting who it claims signed it.
+        if !agg.aggregate_sig.verify_aggregate(&agg.signers, context, &agg.digest) {
+            warn!("Dropping aggregated commitments with an aggregate signature that doesn't verify");
+            return Err(Error::new("invalid aggregate signature"));
+        }
+
+        let roles = agg.signers
+            .iter()
+            .map(|signer| {
+                committee
+                    .iter()
+                    .find(|node| node.public_key == *signer)
+                    .map(|node| node.role)
+                    .ok_or_else(|| Error::new("aggregated commitments signed by unknown node"))
+            })
+            .collect::<Result<Vec<Role>>>()?;
+
+        Ok((agg.digest, roles))
     }
 
     /// Subscribe to notifications on our current role in the computation committee.
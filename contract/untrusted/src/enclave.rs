@@ -2,8 +2,9 @@
 use serde_cbor;
 use sgx_types::*;
 
+use ekiden_common::bytes::B256;
 use ekiden_common::error::{Error, Result};
-use ekiden_contract_common::batch::{CallBatch, OutputBatch};
+use ekiden_contract_common::batch::{CallBatch, EncryptedKeyShare, OutputBatch};
 use ekiden_enclave_untrusted::Enclave;
 
 use super::ecall_proxy;
@@ -17,6 +18,19 @@ pub trait EnclaveContract {
 
     /// Invoke a contract on a batch of calls and return the (encrypted) outputs.
     fn contract_call_batch(&self, batch: &CallBatch) -> Result<OutputBatch>;
+
+    /// Invoke a contract on a batch of calls, returning the (encrypted)
+    /// outputs alongside one key share per entry in `recipients`. The
+    /// enclave generates a fresh symmetric output key, threshold-splits
+    /// it bound to each recipient's public key, and never reveals the
+    /// unsplit key outside the enclave -- reading the outputs back
+    /// requires a threshold of those recipients to cooperate, rather
+    /// than any single node holding the whole key.
+    fn contract_call_batch_sealed(
+        &self,
+        batch: &CallBatch,
+        recipients: &[B256],
+    ) -> Result<(OutputBatch, Vec<EncryptedKeyShare>)>;
 }
 
 impl EnclaveContract for Enclave {
@@ -89,4 +103,60 @@ impl EnclaveContract for Enclave {
 
         Ok(outputs)
     }
+
+    fn contract_call_batch_sealed(
+        &self,
+        batch: &CallBatch,
+        recipients: &[B256],
+    ) -> Result<(OutputBatch, Vec<EncryptedKeyShare>)> {
+        // Encode input batch and recipient list.
+        let batch_encoded = serde_cbor::to_vec(batch)?;
+        let recipients_encoded = serde_cbor::to_vec(recipients)?;
+
+        // Reserve space up to the maximum size of serialized response.
+        let mut response: Vec<u8> = Vec::with_capacity(Self::MAX_RESPONSE_SIZE * 1024);
+        let mut response_length = 0;
+
+        let status = unsafe {
+            ecall_proxy::contract_call_batch_sealed(
+                self.get_id(),
+                batch_encoded.as_ptr() as *const u8,
+                batch_encoded.len(),
+                recipients_encoded.as_ptr() as *const u8,
+                recipients_encoded.len(),
+                response.as_mut_ptr() as *mut u8,
+                response.capacity(),
+                &mut response_length,
+            )
+        };
+
+        if status != sgx_status_t::SGX_SUCCESS {
+            return Err(Error::new(format!(
+                "contract_call_batch_sealed: failed to call enclave ({})",
+                status
+            )));
+        }
+
+        unsafe {
+            response.set_len(response_length);
+        }
+
+        let (outputs, key_shares): (OutputBatch, Vec<EncryptedKeyShare>) =
+            serde_cbor::from_slice(&response)?;
+
+        // Assert equal number of responses, fail otherwise (corrupted response).
+        if outputs.len() != batch.len() {
+            return Err(Error::new(
+                "contract_call_batch_sealed: corrupted response (response count != request count)",
+            ));
+        }
+
+        if key_shares.len() != recipients.len() {
+            return Err(Error::new(
+                "contract_call_batch_sealed: corrupted response (key share count != recipient count)",
+            ));
+        }
+
+        Ok((outputs, key_shares))
+    }
 }
@@ -8,12 +8,33 @@ use crate::{
     storage::mkvs::urkel::{cache::*, sync::*, tree::*, utils::*},
 };
 
+mod chunking;
+
+use self::chunking::{Chunker, CHUNKING_THRESHOLD};
+
+/// One slot of the intrusive doubly-linked LRU list. Indexed by slot
+/// number (`extra - 1`); `0` is never a valid slot number, so it doubles
+/// as `CacheItem::get_cache_extra`'s "not currently in the list" sentinel
+/// for `prev`/`next`/`head`/`tail`.
+struct LRUSlot<V> {
+    prev: u64,
+    next: u64,
+    item: Rc<RefCell<V>>,
+}
+
+/// A least-recently-used list where every touch, insertion, and eviction
+/// is O(1): each cached item stores its own slot number (via the existing
+/// `get_cache_extra`/`set_cache_extra` hook) into a slab of doubly-linked
+/// slots, rather than a `BTreeMap<u64, _>` keyed by an ever-increasing
+/// sequence number.
 struct LRUList<V>
 where
     V: CacheItem,
 {
-    pub list: BTreeMap<u64, Rc<RefCell<V>>>,
-    pub seq_next: u64,
+    slots: Vec<Option<LRUSlot<V>>>,
+    free_slots: Vec<u64>,
+    head: u64,
+    tail: u64,
     pub size: usize,
     pub capacity: usize,
 }
@@ -24,32 +45,91 @@ where
 {
     pub fn new(capacity: usize) -> LRUList<V> {
         LRUList {
-            list: BTreeMap::new(),
-            seq_next: 1,
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            head: 0,
+            tail: 0,
             size: 0,
             capacity: capacity,
         }
     }
 
+    fn slot(&self, extra: u64) -> &LRUSlot<V> {
+        self.slots[(extra - 1) as usize].as_ref().unwrap()
+    }
+
+    fn slot_mut(&mut self, extra: u64) -> &mut LRUSlot<V> {
+        self.slots[(extra - 1) as usize].as_mut().unwrap()
+    }
+
+    fn alloc_slot(&mut self, item: Rc<RefCell<V>>) -> u64 {
+        let slot = LRUSlot {
+            prev: 0,
+            next: 0,
+            item: item,
+        };
+        if let Some(extra) = self.free_slots.pop() {
+            self.slots[(extra - 1) as usize] = Some(slot);
+            extra
+        } else {
+            self.slots.push(Some(slot));
+            self.slots.len() as u64
+        }
+    }
+
+    fn free_slot(&mut self, extra: u64) {
+        self.slots[(extra - 1) as usize] = None;
+        self.free_slots.push(extra);
+    }
+
+    fn detach(&mut self, extra: u64) {
+        let (prev, next) = {
+            let slot = self.slot(extra);
+            (slot.prev, slot.next)
+        };
+        if prev != 0 {
+            self.slot_mut(prev).next = next;
+        } else {
+            self.head = next;
+        }
+        if next != 0 {
+            self.slot_mut(next).prev = prev;
+        } else {
+            self.tail = prev;
+        }
+    }
+
+    fn attach_front(&mut self, extra: u64) {
+        let old_head = self.head;
+        {
+            let slot = self.slot_mut(extra);
+            slot.prev = 0;
+            slot.next = old_head;
+        }
+        if old_head != 0 {
+            self.slot_mut(old_head).prev = extra;
+        } else {
+            self.tail = extra;
+        }
+        self.head = extra;
+    }
+
     fn add_to_front(&mut self, val: Rc<RefCell<V>>) {
-        let mut val_ref = val.borrow_mut();
-        if val_ref.get_cache_extra() == 0 {
-            self.size += val_ref.get_cached_size();
+        if val.borrow().get_cache_extra() == 0 {
+            self.size += val.borrow().get_cached_size();
         }
-        val_ref.set_cache_extra(self.seq_next);
-        self.list.insert(val_ref.get_cache_extra(), val.clone());
-        self.seq_next += 1;
+        let extra = self.alloc_slot(val.clone());
+        val.borrow_mut().set_cache_extra(extra);
+        self.attach_front(extra);
     }
 
     fn move_to_front(&mut self, val: Rc<RefCell<V>>) -> bool {
-        let mut val_ref = val.borrow_mut();
-        if val_ref.get_cache_extra() == 0 {
+        let extra = val.borrow().get_cache_extra();
+        if extra == 0 {
             false
         } else {
-            self.list.remove(&val_ref.get_cache_extra());
-            val_ref.set_cache_extra(self.seq_next);
-            self.list.insert(val_ref.get_cache_extra(), val.clone());
-            self.seq_next += 1;
+            self.detach(extra);
+            self.attach_front(extra);
             true
         }
     }
@@ -59,15 +139,12 @@ where
         if extra == 0 {
             false
         } else {
-            match self.list.remove(&extra) {
-                None => false,
-                Some(val) => {
-                    let mut val = val.borrow_mut();
-                    val.set_cache_extra(0);
-                    self.size -= val.get_cached_size();
-                    true
-                }
-            }
+            self.detach(extra);
+            self.free_slot(extra);
+            let mut val_ref = val.borrow_mut();
+            val_ref.set_cache_extra(0);
+            self.size -= val_ref.get_cached_size();
+            true
         }
     }
 
@@ -75,9 +152,8 @@ where
         let mut evicted: Vec<Rc<RefCell<V>>> = Vec::new();
         if self.capacity > 0 {
             let target_size = val.borrow().get_cached_size();
-            while !self.list.is_empty() && self.capacity - self.size < target_size {
-                let lowest = self.list.keys().next().unwrap();
-                let item = self.list.get(lowest).unwrap().clone();
+            while self.tail != 0 && self.capacity - self.size < target_size {
+                let item = self.slot(self.tail).item.clone();
                 if self.remove(item.clone()) {
                     evicted.push(item);
                 }
@@ -87,6 +163,31 @@ where
     }
 }
 
+/// Point-in-time snapshot of a `Cache`'s occupancy and effectiveness,
+/// returned by `Cache::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of internal nodes currently cached.
+    pub internal_node_count: u64,
+    /// Number of leaf nodes currently cached.
+    pub leaf_node_count: u64,
+    /// Total size, in bytes, of leaf values currently cached.
+    pub leaf_value_size: usize,
+    /// Number of `use_node`/`use_value` calls that found their target
+    /// already cached.
+    pub hits: u64,
+    /// Number of `use_node`/`use_value` calls that did not.
+    pub misses: u64,
+    /// Number of nodes/values evicted to make room for a new entry.
+    pub evictions: u64,
+    /// Total size, in bytes, of everything `evictions` has evicted.
+    pub bytes_evicted: u64,
+    /// Number of `prefetch` calls whose subtree was actually returned by
+    /// the read syncer (as opposed to `Unsupported` or disabled via
+    /// `prefetch_depth == 0`).
+    pub prefetch_satisfied: u64,
+}
+
 /// Cache implementation with a simple LRU eviction strategy.
 pub struct LRUCache {
     read_syncer: Box<dyn ReadSync>,
@@ -101,6 +202,31 @@ pub struct LRUCache {
 
     lru_values: LRUList<ValuePointer>,
     lru_nodes: LRUList<NodePointer>,
+
+    /// Content-defined chunks carved out of values that went through
+    /// `chunk_and_cache`, kept in their own LRU list (distinct from
+    /// `lru_values`) so that a chunk shared by several large values is
+    /// only stored once and evicted independently of any one of them.
+    chunk_values: LRUList<ValuePointer>,
+    /// Chunk content hash -> cached chunk, for dedup lookups in
+    /// `chunk_and_cache`.
+    chunk_index: BTreeMap<Hash, ValuePtrRef>,
+    /// Whole-value content hash -> its ordered list of chunk hashes, for
+    /// values that went through `chunk_and_cache`. Lets `deref_value_ptr`
+    /// reassemble a value from already-cached chunks instead of going
+    /// back to the read syncer, as long as every one of its chunks is
+    /// still resident.
+    chunk_manifests: BTreeMap<Hash, Vec<Hash>>,
+
+    /// Counters backing `stats()`, updated at the `use_node`/`use_value`/
+    /// `evict_for_val` call sites so operators can tune `node_capacity`/
+    /// `value_capacity` and `prefetch_depth` against real workloads
+    /// instead of guessing from counts and sizes alone.
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+    bytes_evicted: u64,
+    prefetch_satisfied: u64,
 }
 
 impl LRUCache {
@@ -132,6 +258,16 @@ impl LRUCache {
 
             lru_values: LRUList::new(value_capacity),
             lru_nodes: LRUList::new(node_capacity),
+
+            chunk_values: LRUList::new(value_capacity),
+            chunk_index: BTreeMap::new(),
+            chunk_manifests: BTreeMap::new(),
+
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+            bytes_evicted: 0,
+            prefetch_satisfied: 0,
         })
     }
 
@@ -149,15 +285,72 @@ impl LRUCache {
         }))
     }
 
-    fn new_value_ptr(&self, val: Value) -> ValuePtrRef {
+    fn new_value_ptr(&mut self, val: Value) -> ValuePtrRef {
+        self.chunk_and_cache(&val);
         Rc::new(RefCell::new(ValuePointer {
             value: Some(val.clone()),
             ..Default::default()
         }))
     }
 
+    /// Split `val` into content-defined chunks and record them in
+    /// `chunk_values`/`chunk_index`, plus `val`'s manifest in
+    /// `chunk_manifests`, so a later `deref_value_ptr` for the same
+    /// content hash can rebuild `val` out of cached chunks via
+    /// `reconstruct_from_chunks` rather than re-fetching it whole.
+    /// Values at or below `CHUNKING_THRESHOLD` are left alone.
+    fn chunk_and_cache(&mut self, val: &Value) {
+        if val.len() <= CHUNKING_THRESHOLD {
+            return;
+        }
+
+        let mut manifest = Vec::new();
+        for range in Chunker::new().split(val) {
+            let chunk_hash = Hash::digest_bytes(&val[range.clone()]);
+            manifest.push(chunk_hash);
+            if let Some(existing) = self.chunk_index.get(&chunk_hash).cloned() {
+                self.chunk_values.move_to_front(existing);
+                continue;
+            }
+
+            let chunk_ptr = Rc::new(RefCell::new(ValuePointer {
+                value: Some(val[range].to_vec()),
+                hash: chunk_hash,
+                clean: true,
+                ..Default::default()
+            }));
+            for evicted in self.chunk_values.evict_for_val(chunk_ptr.clone()).iter() {
+                self.chunk_index.remove(&evicted.borrow().hash);
+            }
+            self.chunk_values.add_to_front(chunk_ptr.clone());
+            self.chunk_index.insert(chunk_hash, chunk_ptr);
+        }
+        self.chunk_manifests.insert(Hash::digest_bytes(val), manifest);
+    }
+
+    /// Try to rebuild the value hashing to `hash` purely from
+    /// already-cached chunks, without touching the read syncer. Returns
+    /// `None` if `hash` was never chunked, or if any of its chunks has
+    /// since been evicted from `chunk_values`.
+    fn reconstruct_from_chunks(&mut self, hash: Hash) -> Option<Value> {
+        let manifest = self.chunk_manifests.get(&hash)?.clone();
+        let mut value = Vec::new();
+        for chunk_hash in &manifest {
+            let chunk_ptr = self.chunk_index.get(chunk_hash)?.clone();
+            self.chunk_values.move_to_front(chunk_ptr.clone());
+            value.extend_from_slice(chunk_ptr.borrow().value.as_ref()?);
+        }
+        Some(value)
+    }
+
     fn use_node(&mut self, node: NodePtrRef) -> bool {
-        self.lru_nodes.move_to_front(node)
+        let hit = self.lru_nodes.move_to_front(node);
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        hit
     }
 
     fn remove_node(&mut self, ptr: NodePtrRef) {
@@ -180,7 +373,13 @@ impl LRUCache {
     }
 
     fn use_value(&mut self, val: ValuePtrRef) -> bool {
-        self.lru_values.move_to_front(val)
+        let hit = self.lru_values.move_to_front(val);
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        hit
     }
 
     fn _reconstruct_summary(
@@ -236,6 +435,11 @@ impl Cache for LRUCache {
             internal_node_count: self.internal_node_count,
             leaf_node_count: self.leaf_node_count,
             leaf_value_size: self.lru_values.size,
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+            bytes_evicted: self.bytes_evicted,
+            prefetch_satisfied: self.prefetch_satisfied,
         }
     }
 
@@ -374,6 +578,56 @@ impl Cache for LRUCache {
         Ok(ptr.node.clone())
     }
 
+    /// Batched counterpart to `deref_node_ptr`'s `key: None` form: resolve
+    /// every not-yet-cached pointer in `frontier` with a single
+    /// `read_syncer.get_nodes` call, bounded by
+    /// `read_syncer.get_batch_size()`, instead of one `get_node` round
+    /// trip per pointer. Pointers that are already cached, dirty, or null
+    /// are resolved locally and never make it into the batched request.
+    fn deref_node_ptrs(
+        &mut self,
+        ctx: &Arc<Context>,
+        frontier: &[(NodeID, NodePtrRef)],
+    ) -> Fallible<Vec<Option<NodeRef>>> {
+        let mut results = vec![None; frontier.len()];
+        let mut pending_ids = Vec::new();
+        let mut pending_indices = Vec::new();
+
+        for (index, (node_id, ptr)) in frontier.iter().enumerate() {
+            let ptr_ref = ptr.borrow();
+            if let Some(ref node) = &ptr_ref.node {
+                results[index] = Some(node.clone());
+                continue;
+            }
+            if !ptr_ref.clean || ptr_ref.is_null() {
+                continue;
+            }
+            pending_ids.push(node_id.clone());
+            pending_indices.push(index);
+        }
+
+        let batch_size = self.read_syncer.get_batch_size().max(1);
+        for (id_chunk, index_chunk) in pending_ids
+            .chunks(batch_size)
+            .zip(pending_indices.chunks(batch_size))
+        {
+            let nodes = self.read_syncer.get_nodes(
+                Context::create_child(ctx),
+                self.sync_root,
+                id_chunk,
+            )?;
+            for (node_ref, &index) in nodes.into_iter().zip(index_chunk) {
+                node_ref
+                    .borrow_mut()
+                    .validate(frontier[index].1.borrow().hash)?;
+                frontier[index].1.borrow_mut().node = Some(node_ref.clone());
+                results[index] = Some(node_ref);
+            }
+        }
+
+        Ok(results)
+    }
+
     fn deref_value_ptr(&mut self, ctx: &Arc<Context>, val: ValuePtrRef) -> Fallible<Option<Value>> {
         if self.use_value(val.clone()) || val.borrow().value != None {
             return Ok(val.borrow().value.clone());
@@ -385,13 +639,19 @@ impl Cache for LRUCache {
                 return Ok(None);
             }
 
-            let value =
-                self.read_syncer
-                    .get_value(Context::create_child(ctx), self.sync_root, val.hash)?;
-            val.value = value;
+            val.value = match self.reconstruct_from_chunks(val.hash) {
+                Some(value) => Some(value),
+                None => {
+                    self.read_syncer
+                        .get_value(Context::create_child(ctx), self.sync_root, val.hash)?
+                }
+            };
             let hash = val.hash;
             val.validate(hash)?;
         }
+        if let Some(value) = val.borrow().value.clone() {
+            self.chunk_and_cache(&value);
+        }
         self.commit_value(val.clone());
 
         Ok(val.borrow().value.clone())
@@ -409,6 +669,8 @@ impl Cache for LRUCache {
         }
 
         for node in self.lru_nodes.evict_for_val(ptr.clone()).iter() {
+            self.evictions += 1;
+            self.bytes_evicted += node.borrow().get_cached_size() as u64;
             self.remove_node(node.clone());
         }
         self.lru_nodes.add_to_front(ptr.clone());
@@ -432,7 +694,10 @@ impl Cache for LRUCache {
             return;
         }
 
-        self.lru_values.evict_for_val(ptr.clone());
+        for evicted in self.lru_values.evict_for_val(ptr.clone()).iter() {
+            self.evictions += 1;
+            self.bytes_evicted += evicted.borrow().get_cached_size() as u64;
+        }
         self.lru_values.add_to_front(ptr.clone());
     }
 
@@ -495,6 +760,8 @@ impl Cache for LRUCache {
             }
             Ok(ref st) => st,
         };
-        self.reconstruct_subtree(ctx, subtree_root, st, 0, self.prefetch_depth)
+        let ptr = self.reconstruct_subtree(ctx, subtree_root, st, 0, self.prefetch_depth)?;
+        self.prefetch_satisfied += 1;
+        Ok(ptr)
     }
 }
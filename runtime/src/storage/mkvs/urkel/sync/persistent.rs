@@ -0,0 +1,183 @@
+//! A disk-backed `ReadSync` that persists every node, subtree fragment,
+//! and value it ever serves, so that a restarted process can warm its
+//! `LRUCache` from local storage instead of re-fetching the whole tree
+//! from a remote syncer.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use failure::Fallible;
+use io_context::Context;
+
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::urkel::{sync::*, tree::*},
+};
+
+/// Minimal, content-addressed storage contract a persistent `ReadSync`
+/// backend must provide. Kept small -- get/put/batch/iterate -- so that
+/// an LMDB-backed store, a SQLite-backed store, or any other embedded
+/// key-value store can sit behind `PersistentReadSyncer` without the rest
+/// of this module caring which one is in use.
+pub trait Backend: Send + Sync {
+    /// Open (creating if necessary) the backend rooted at `path`.
+    fn open(path: &str) -> Fallible<Self>
+    where
+        Self: Sized;
+
+    /// Fetch the bytes stored under `key`, if any.
+    fn get(&self, key: &Hash) -> Fallible<Option<Vec<u8>>>;
+
+    /// Store `value` under `key`, overwriting any prior entry.
+    fn put(&self, key: Hash, value: Vec<u8>) -> Fallible<()>;
+
+    /// Store every `(key, value)` pair as a single transaction, so a
+    /// crash can never leave only part of a fetched subtree persisted.
+    fn write_batch(&self, batch: Vec<(Hash, Vec<u8>)>) -> Fallible<()>;
+
+    /// Every key currently stored. Used for diagnostics and
+    /// anti-entropy repair; not on any hot path.
+    fn iter_keys(&self) -> Fallible<Vec<Hash>>;
+}
+
+/// An in-process `Backend` kept behind a `Mutex<HashMap<..>>`, used where
+/// no real embedded store is linked in. A production deployment should
+/// swap in an LMDB- or SQLite-backed `Backend` with the same durability
+/// contract; nothing above this trait needs to change to do so.
+pub struct MemoryBackend {
+    entries: Mutex<HashMap<Hash, Vec<u8>>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        MemoryBackend {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn open(_path: &str) -> Fallible<Self> {
+        Ok(MemoryBackend::new())
+    }
+
+    fn get(&self, key: &Hash) -> Fallible<Option<Vec<u8>>> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn put(&self, key: Hash, value: Vec<u8>) -> Fallible<()> {
+        self.entries.lock().unwrap().insert(key, value);
+        Ok(())
+    }
+
+    fn write_batch(&self, batch: Vec<(Hash, Vec<u8>)>) -> Fallible<()> {
+        let mut entries = self.entries.lock().unwrap();
+        for (key, value) in batch {
+            entries.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn iter_keys(&self) -> Fallible<Vec<Hash>> {
+        Ok(self.entries.lock().unwrap().keys().cloned().collect())
+    }
+}
+
+/// Derive the local storage key for a `get_node`/`get_subtree`/`get_path`
+/// request. Unlike values, nodes and subtree fragments aren't addressed
+/// by their own content hash at the `ReadSync` boundary -- the caller only
+/// supplies the root they're resolving against and the position within
+/// it -- so the backend key is a hash over that request shape instead.
+fn request_key(parts: &[&[u8]]) -> Hash {
+    let mut preimage = Vec::new();
+    for part in parts {
+        preimage.extend_from_slice(part);
+    }
+    Hash::digest_bytes(&preimage)
+}
+
+/// A `ReadSync` that serves nodes, subtree fragments, and values from a
+/// local `Backend` first, only falling through to `remote` -- and
+/// persisting whatever comes back -- on a miss. Layered underneath an
+/// `LRUCache`, this turns what would otherwise be a full remote refetch
+/// on every process restart into a warm, local-only read.
+pub struct PersistentReadSyncer<B: Backend> {
+    backend: B,
+    remote: Box<dyn ReadSync>,
+}
+
+impl<B: Backend> PersistentReadSyncer<B> {
+    pub fn new(backend: B, remote: Box<dyn ReadSync>) -> Self {
+        PersistentReadSyncer { backend, remote }
+    }
+}
+
+impl<B: Backend> ReadSync for PersistentReadSyncer<B> {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_subtree(
+        &mut self,
+        ctx: Context,
+        root_hash: Hash,
+        id: NodeID,
+        max_depth: u8,
+    ) -> Fallible<Subtree> {
+        let key = request_key(&[
+            root_hash.as_ref(),
+            id.path.as_ref(),
+            &[id.depth, max_depth],
+        ]);
+        if let Some(bytes) = self.backend.get(&key)? {
+            return Subtree::unmarshal_binary(&bytes);
+        }
+
+        let subtree = self.remote.get_subtree(ctx, root_hash, id, max_depth)?;
+        self.backend.put(key, subtree.marshal_binary()?)?;
+        Ok(subtree)
+    }
+
+    fn get_path(
+        &mut self,
+        ctx: Context,
+        root_hash: Hash,
+        key: &Key,
+        start_depth: u8,
+    ) -> Fallible<Subtree> {
+        let cache_key = request_key(&[root_hash.as_ref(), key.as_ref(), &[start_depth]]);
+        if let Some(bytes) = self.backend.get(&cache_key)? {
+            return Subtree::unmarshal_binary(&bytes);
+        }
+
+        let subtree = self.remote.get_path(ctx, root_hash, key, start_depth)?;
+        self.backend.put(cache_key, subtree.marshal_binary()?)?;
+        Ok(subtree)
+    }
+
+    fn get_node(&mut self, ctx: Context, root_hash: Hash, id: NodeID) -> Fallible<NodeRef> {
+        let key = request_key(&[root_hash.as_ref(), id.path.as_ref(), &[id.depth]]);
+        if let Some(bytes) = self.backend.get(&key)? {
+            return NodeBox::unmarshal_binary(&bytes);
+        }
+
+        let node = self.remote.get_node(ctx, root_hash, id)?;
+        self.backend.put(key, node.borrow().marshal_binary()?)?;
+        Ok(node)
+    }
+
+    fn get_value(&mut self, ctx: Context, root_hash: Hash, id: Hash) -> Fallible<Option<Value>> {
+        // Values are already content-addressed by `id`, so unlike nodes
+        // and subtrees there is no need to derive a synthetic key.
+        if let Some(value) = self.backend.get(&id)? {
+            return Ok(Some(value));
+        }
+
+        let value = self.remote.get_value(ctx, root_hash, id)?;
+        if let Some(ref value) = value {
+            self.backend.put(id, value.clone())?;
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,230 @@
+//! A disk-backed `Backend` (see `persistent::Backend`) that never rewrites
+//! bytes it has already written, following the append-log strategy
+//! `dirstate-v2` uses in Mercurial's `hg-core`: every `put` appends the
+//! serialized entry to a single data file and records where it landed in
+//! a small in-memory root->offset index, rather than updating any
+//! existing record in place. `get` simply seeks to the recorded offset
+//! and reads `length` bytes back out.
+//!
+//! Because old bytes are never touched, a reader holding an offset into
+//! an older version of the log keeps working even after later `put`s
+//! land -- the invariant this module is built around. The cost is that
+//! every superseded entry becomes dead weight the log can never reclaim
+//! on its own; `AppendLogBackend` tracks how many bytes that is and,
+//! once the unreachable fraction crosses `compaction_ratio` (by default
+//! the same ~0.5 dirstate-v2 uses), rewrites the log to a fresh file
+//! holding only the entries still referenced by the index before
+//! swapping it in.
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use failure::Fallible;
+
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::urkel::{cache::*, sync::*, tree::*},
+};
+
+/// Fraction of the log's total bytes that may belong to superseded
+/// entries before the next `put` triggers compaction, matching
+/// dirstate-v2's default rewrite threshold.
+pub const DEFAULT_COMPACTION_RATIO: f64 = 0.5;
+
+/// Location and size of one entry within the append log's data file.
+#[derive(Clone, Copy)]
+struct IndexEntry {
+    offset: u64,
+    length: u64,
+}
+
+struct AppendLogInner {
+    path: PathBuf,
+    file: File,
+    /// Where the next `put` will append; always equal to the data
+    /// file's current length.
+    tail: u64,
+    index: HashMap<Hash, IndexEntry>,
+    /// Total bytes belonging to entries that have since been
+    /// overwritten in the index and so can no longer be reached by any
+    /// key, but that still occupy space in the data file.
+    unreachable_bytes: u64,
+    compaction_ratio: f64,
+}
+
+impl AppendLogInner {
+    fn open(path: &Path, compaction_ratio: f64) -> Fallible<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let tail = file.metadata()?.len();
+        Ok(AppendLogInner {
+            path: path.to_path_buf(),
+            file,
+            tail,
+            index: HashMap::new(),
+            unreachable_bytes: 0,
+            compaction_ratio,
+        })
+    }
+
+    fn get(&mut self, key: &Hash) -> Fallible<Option<Vec<u8>>> {
+        let entry = match self.index.get(key) {
+            None => return Ok(None),
+            Some(entry) => *entry,
+        };
+        let mut buf = vec![0u8; entry.length as usize];
+        self.file.seek(SeekFrom::Start(entry.offset))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(Some(buf))
+    }
+
+    fn put(&mut self, key: Hash, value: Vec<u8>) -> Fallible<()> {
+        let entry = IndexEntry {
+            offset: self.tail,
+            length: value.len() as u64,
+        };
+        self.file.write_all(&value)?;
+        self.file.flush()?;
+        self.tail += entry.length;
+
+        if let Some(old) = self.index.insert(key, entry) {
+            self.unreachable_bytes += old.length;
+        }
+
+        if self.unreachable_fraction() > self.compaction_ratio {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn unreachable_fraction(&self) -> f64 {
+        if self.tail == 0 {
+            0.0
+        } else {
+            self.unreachable_bytes as f64 / self.tail as f64
+        }
+    }
+
+    /// Copy every still-reachable entry to a fresh file in index order
+    /// and swap it in for `self.file`. Readers that already hold an
+    /// offset from before compaction are unaffected by this rewrite --
+    /// they are simply left referencing the old file's inode, which on
+    /// a POSIX filesystem survives the rename below until they close
+    /// it -- but any offset handed out afterwards refers to the new
+    /// file, which is why the index is remapped as part of the same
+    /// operation rather than published separately.
+    fn compact(&mut self) -> Fallible<()> {
+        let tmp_path = self.path.with_extension("compact");
+        let mut tmp_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+
+        let mut new_index = HashMap::with_capacity(self.index.len());
+        let mut tail = 0u64;
+        for (key, entry) in self.index.iter() {
+            let mut buf = vec![0u8; entry.length as usize];
+            self.file.seek(SeekFrom::Start(entry.offset))?;
+            self.file.read_exact(&mut buf)?;
+            tmp_file.write_all(&buf)?;
+
+            new_index.insert(
+                key.clone(),
+                IndexEntry {
+                    offset: tail,
+                    length: entry.length,
+                },
+            );
+            tail += entry.length;
+        }
+        tmp_file.flush()?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.file = OpenOptions::new().read(true).append(true).open(&self.path)?;
+        self.index = new_index;
+        self.tail = tail;
+        self.unreachable_bytes = 0;
+        Ok(())
+    }
+}
+
+/// An append-only, content-addressed `Backend` for `PersistentReadSyncer`
+/// (or any other `Backend` consumer). Every `put` is flushed to the data
+/// file, but the key->offset index lives only in memory and is not
+/// rebuilt from the data file on open: a reopened log starts with an
+/// empty index, so `get` does not find entries written before the
+/// reopen.
+pub struct AppendLogBackend {
+    inner: Mutex<AppendLogInner>,
+}
+
+impl AppendLogBackend {
+    /// Open (creating if necessary) an append log at `path`, compacting
+    /// once more than `compaction_ratio` of its bytes become
+    /// unreachable instead of the `DEFAULT_COMPACTION_RATIO` `open`
+    /// (the `Backend::open` trait method) always uses.
+    pub fn open_with_ratio(path: &str, compaction_ratio: f64) -> Fallible<Self> {
+        Ok(AppendLogBackend {
+            inner: Mutex::new(AppendLogInner::open(Path::new(path), compaction_ratio)?),
+        })
+    }
+
+    /// Fraction of the log's current data file that is unreachable
+    /// garbage, for tuning `compaction_ratio` against real workloads.
+    pub fn unreachable_fraction(&self) -> f64 {
+        self.inner.lock().unwrap().unreachable_fraction()
+    }
+}
+
+impl Backend for AppendLogBackend {
+    fn open(path: &str) -> Fallible<Self> {
+        AppendLogBackend::open_with_ratio(path, DEFAULT_COMPACTION_RATIO)
+    }
+
+    fn get(&self, key: &Hash) -> Fallible<Option<Vec<u8>>> {
+        self.inner.lock().unwrap().get(key)
+    }
+
+    fn put(&self, key: Hash, value: Vec<u8>) -> Fallible<()> {
+        self.inner.lock().unwrap().put(key, value)
+    }
+
+    fn write_batch(&self, batch: Vec<(Hash, Vec<u8>)>) -> Fallible<()> {
+        let mut inner = self.inner.lock().unwrap();
+        for (key, value) in batch {
+            inner.put(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn iter_keys(&self) -> Fallible<Vec<Hash>> {
+        Ok(self.inner.lock().unwrap().index.keys().cloned().collect())
+    }
+}
+
+/// Open (or create) an Urkel tree whose read syncer is a
+/// `PersistentReadSyncer` over an `AppendLogBackend` at `path`, with
+/// `NoopReadSyncer` as its remote. There is no remote fallback -- a log
+/// that doesn't hold a requested entry reports `SyncerError::Unsupported`,
+/// the same as `NoopReadSyncer` alone would. Entries reach the log only
+/// when that remote returns them, which `NoopReadSyncer` never does, and
+/// nodes or values committed to the tree are not written to the log;
+/// entries from an earlier process are not found either, since the log's
+/// index is not reloaded on open (see `AppendLogBackend`).
+///
+/// The `LRUCache` sitting in front of the log is given unlimited node
+/// and value capacity (a capacity of 0), so it never evicts anything.
+pub fn open_persistent(path: &str) -> Fallible<UrkelTree> {
+    let backend = AppendLogBackend::open(path)?;
+    let syncer = PersistentReadSyncer::new(backend, Box::new(NoopReadSyncer {}));
+    Ok(UrkelTree::new(LRUCache::new(0, 0, Box::new(syncer))))
+}
@@ -0,0 +1,198 @@
+//! A `ReadSync` that demand-pages Urkel tree state from the
+//! storage-serving members of the current computation committee, rather
+//! than forcing every consumer to hold the full state locally the way
+//! `NoopReadSyncer` does by rejecting every request.
+//!
+//! Committee membership is supplied from the outside via
+//! `update_committee` rather than watched directly -- this module has no
+//! reason to depend on the scheduler/compute crates that actually track
+//! committee rotation. The caller that already watches
+//! `ComputationGroup` membership changes (the same committee stream used
+//! to keep `node_group` current) is expected to forward every update
+//! here as well.
+use std::{any::Any, sync::Arc, sync::Mutex};
+
+use failure::Fallible;
+use io_context::Context;
+
+use crate::{
+    common::crypto::hash::Hash,
+    storage::mkvs::urkel::{sync::*, tree::*},
+};
+
+/// Number of nodes/values `RemoteReadSyncer` asks for in a single
+/// `get_nodes`/`get_values` request. Bounds how many pointers a caller
+/// like `UrkelTree::_get_subtree` batches into one round trip; kept well
+/// above the 3-way branching factor of a single tree level so an entire
+/// level is almost always satisfied in one request regardless of this
+/// value.
+const REMOTE_BATCH_SIZE: usize = 64;
+
+/// Identifies one storage-serving committee member `RemoteReadSyncer` can
+/// route requests to. Opaque beyond what's needed to open a connection --
+/// networking specifics live entirely behind `PeerConnector`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct StoragePeer {
+    /// Committee member's public key, used only to identify the peer in
+    /// retry bookkeeping and logs.
+    pub public_key: Hash,
+}
+
+/// Opens a `ReadSync` connection to a given committee member. Kept as a
+/// trait -- rather than this module owning a gRPC channel directly -- so
+/// tests can substitute an in-process `ReadSync` instead of a real
+/// connection, the same way `persistent::Backend` is abstracted away
+/// from any particular embedded store.
+pub trait PeerConnector: Send + Sync {
+    /// Connect (or reuse an existing connection) to `peer`.
+    fn connect(&self, peer: &StoragePeer) -> Fallible<Box<dyn ReadSync>>;
+}
+
+/// A `ReadSync` that serves every request by fetching from a
+/// storage-serving member of the current computation committee, so a
+/// light compute node can lazily materialize only the tree paths it
+/// touches instead of requiring full local replication. Retries against
+/// another committee member on `SyncerError::Unsupported` or any other
+/// transport failure, giving up only once every known peer has failed.
+pub struct RemoteReadSyncer {
+    connector: Box<dyn PeerConnector>,
+    peers: Mutex<Vec<StoragePeer>>,
+}
+
+impl RemoteReadSyncer {
+    pub fn new(connector: Box<dyn PeerConnector>) -> Self {
+        RemoteReadSyncer {
+            connector,
+            peers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Replace the set of candidate storage peers, e.g. in response to a
+    /// `ComputationGroup` committee update delivered over the same
+    /// stream this crate already watches for membership changes.
+    pub fn update_committee(&self, peers: Vec<StoragePeer>) {
+        *self.peers.lock().unwrap() = peers;
+    }
+
+    /// Try `op` against each candidate peer in turn, moving on to the
+    /// next one on `SyncerError::Unsupported` or any other transport
+    /// failure, and failing only once every peer has been tried.
+    fn try_peers<T>(
+        &self,
+        ctx: &Arc<Context>,
+        mut op: impl FnMut(Context, &mut dyn ReadSync) -> Fallible<T>,
+    ) -> Fallible<T> {
+        let peers = self.peers.lock().unwrap().clone();
+        if peers.is_empty() {
+            return Err(SyncerError::Unsupported.into());
+        }
+
+        let mut last_error = None;
+        for peer in &peers {
+            let mut client = match self.connector.connect(peer) {
+                Ok(client) => client,
+                Err(error) => {
+                    last_error = Some(error);
+                    continue;
+                }
+            };
+
+            match op(Context::create_child(ctx), client.as_mut()) {
+                Ok(result) => return Ok(result),
+                Err(error) => {
+                    last_error = Some(error);
+                    continue;
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| SyncerError::Unsupported.into()))
+    }
+}
+
+impl ReadSync for RemoteReadSyncer {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn get_subtree(
+        &mut self,
+        ctx: Context,
+        root_hash: Hash,
+        id: NodeID,
+        max_depth: u8,
+    ) -> Fallible<Subtree> {
+        let ctx = ctx.freeze();
+        let subtree = self.try_peers(&ctx, |child_ctx, client| {
+            client.get_subtree(child_ctx, root_hash, id.clone(), max_depth)
+        })?;
+        if !subtree.root.valid {
+            return Err(SyncerError::InvalidRoot.into());
+        }
+        Ok(subtree)
+    }
+
+    fn get_path(
+        &mut self,
+        ctx: Context,
+        root_hash: Hash,
+        key: &Key,
+        start_depth: u8,
+    ) -> Fallible<Subtree> {
+        let ctx = ctx.freeze();
+        let subtree = self.try_peers(&ctx, |child_ctx, client| {
+            client.get_path(child_ctx, root_hash, key, start_depth)
+        })?;
+        if !subtree.root.valid {
+            return Err(SyncerError::InvalidRoot.into());
+        }
+        Ok(subtree)
+    }
+
+    fn get_node(&mut self, ctx: Context, root_hash: Hash, id: NodeID) -> Fallible<NodeRef> {
+        let ctx = ctx.freeze();
+        self.try_peers(&ctx, |child_ctx, client| {
+            client.get_node(child_ctx, root_hash, id.clone())
+        })
+    }
+
+    fn get_value(&mut self, ctx: Context, root_hash: Hash, id: Hash) -> Fallible<Option<Value>> {
+        let ctx = ctx.freeze();
+        self.try_peers(&ctx, |child_ctx, client| {
+            client.get_value(child_ctx, root_hash, id)
+        })
+    }
+
+    fn get_batch_size(&self) -> usize {
+        REMOTE_BATCH_SIZE
+    }
+
+    /// Overrides the trait's default one-`get_node`-per-id loop with a
+    /// single request per batch of `ids`, so resolving a whole tree
+    /// level costs one round trip to a committee member instead of one
+    /// per sibling.
+    fn get_nodes(
+        &mut self,
+        ctx: Context,
+        root_hash: Hash,
+        ids: &[NodeID],
+    ) -> Fallible<Vec<NodeRef>> {
+        let ctx = ctx.freeze();
+        self.try_peers(&ctx, |child_ctx, client| {
+            client.get_nodes(child_ctx, root_hash, ids)
+        })
+    }
+
+    /// Same batching as `get_nodes`, for value hashes.
+    fn get_values(
+        &mut self,
+        ctx: Context,
+        root_hash: Hash,
+        ids: &[Hash],
+    ) -> Fallible<Vec<Option<Value>>> {
+        let ctx = ctx.freeze();
+        self.try_peers(&ctx, |child_ctx, client| {
+            client.get_values(child_ctx, root_hash, ids)
+        })
+    }
+}
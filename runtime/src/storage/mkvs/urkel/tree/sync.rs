@@ -8,6 +8,14 @@ use crate::{
     storage::mkvs::urkel::{cache::*, sync::*, tree::*},
 };
 
+mod anti_entropy;
+mod diff;
+mod leaf_walker;
+
+pub use self::anti_entropy::LeafDiff;
+pub use self::diff::{DiffEntry, DiffWalker};
+pub use self::leaf_walker::LeafWalker;
+
 impl ReadSync for UrkelTree {
     fn as_any(&self) -> &dyn Any {
         self
@@ -141,6 +149,24 @@ impl UrkelTree {
             ptr.clone(),
             None,
         )?;
+        self._get_subtree_node(ctx, node_ref, depth, path, st, max_depth)
+    }
+
+    /// Continuation of `_get_subtree` once `ptr` has already been
+    /// dereferenced into `node_ref` -- split out so that a whole
+    /// frontier of siblings can be resolved with one batched
+    /// `deref_node_ptrs` call before any of them is recursed into,
+    /// instead of each recursive `_get_subtree` call paying for its own
+    /// `deref_node_ptr` round trip.
+    fn _get_subtree_node(
+        &mut self,
+        ctx: &Arc<Context>,
+        node_ref: Option<NodeRef>,
+        depth: u8,
+        path: Key,
+        st: &mut Subtree,
+        max_depth: u8,
+    ) -> Fallible<SubtreePointer> {
         let node_ref = match node_ref {
             None => {
                 return Ok(SubtreePointer {
@@ -169,30 +195,52 @@ impl UrkelTree {
                     ..Default::default()
                 };
 
-                summary.leaf_node = self._get_subtree(
-                    ctx,
-                    noderef_as!(node_ref, Internal).leaf_node.clone(),
-                    depth,
-                    path.set_bit(depth, false),
-                    st,
-                    max_depth,
-                )?;
-                summary.left = self._get_subtree(
-                    ctx,
-                    noderef_as!(node_ref, Internal).left.clone(),
-                    depth + 1,
-                    path.set_bit(depth, false),
-                    st,
-                    max_depth,
-                )?;
-                summary.right = self._get_subtree(
-                    ctx,
-                    noderef_as!(node_ref, Internal).right.clone(),
-                    depth + 1,
-                    path.set_bit(depth, true),
-                    st,
-                    max_depth,
-                )?;
+                let leaf_path = path.set_bit(depth, false);
+                let left_path = path.set_bit(depth, false);
+                let right_path = path.set_bit(depth, true);
+                let (leaf_ptr, left_ptr, right_ptr) = {
+                    let internal = noderef_as!(node_ref, Internal);
+                    (
+                        internal.leaf_node.clone(),
+                        internal.left.clone(),
+                        internal.right.clone(),
+                    )
+                };
+
+                let frontier = [
+                    (
+                        NodeID {
+                            path: &leaf_path,
+                            depth: depth,
+                        },
+                        leaf_ptr,
+                    ),
+                    (
+                        NodeID {
+                            path: &left_path,
+                            depth: depth + 1,
+                        },
+                        left_ptr,
+                    ),
+                    (
+                        NodeID {
+                            path: &right_path,
+                            depth: depth + 1,
+                        },
+                        right_ptr,
+                    ),
+                ];
+                let mut resolved = self.cache.borrow_mut().deref_node_ptrs(ctx, &frontier)?.into_iter();
+                let leaf_node = resolved.next().unwrap();
+                let left_node = resolved.next().unwrap();
+                let right_node = resolved.next().unwrap();
+
+                summary.leaf_node =
+                    self._get_subtree_node(ctx, leaf_node, depth, leaf_path, st, max_depth)?;
+                summary.left =
+                    self._get_subtree_node(ctx, left_node, depth + 1, left_path, st, max_depth)?;
+                summary.right =
+                    self._get_subtree_node(ctx, right_node, depth + 1, right_path, st, max_depth)?;
 
                 let idx = st.add_summary(&summary)?;
                 return Ok(SubtreePointer {
@@ -229,6 +277,22 @@ impl UrkelTree {
             ptr.clone(),
             Some(key),
         )?;
+        self._get_path_node(ctx, node_ref, depth, key, st)
+    }
+
+    /// Continuation of `_get_path` once `ptr` has already been
+    /// dereferenced into `node_ref` -- split out for the same reason as
+    /// `_get_subtree_node`: it lets `leaf_node`/`left`/`right` be
+    /// resolved with one batched `deref_node_ptrs` call instead of three
+    /// separate recursive `_get_path` round trips.
+    fn _get_path_node(
+        &mut self,
+        ctx: &Arc<Context>,
+        node_ref: Option<NodeRef>,
+        depth: u8,
+        key: &Key,
+        st: &mut Subtree,
+    ) -> Fallible<SubtreePointer> {
         let node_ref = match node_ref {
             None => {
                 return Ok(SubtreePointer {
@@ -257,27 +321,46 @@ impl UrkelTree {
                     ..Default::default()
                 };
 
-                summary.leaf_node = self._get_path(
-                    ctx,
-                    noderef_as!(node_ref, Internal).leaf_node.clone(),
-                    depth,
-                    key,
-                    st,
-                )?;
-                summary.left = self._get_path(
-                    ctx,
-                    noderef_as!(node_ref, Internal).left.clone(),
-                    depth + 1,
-                    key,
-                    st,
-                )?;
-                summary.right = self._get_path(
-                    ctx,
-                    noderef_as!(node_ref, Internal).right.clone(),
-                    depth + 1,
-                    key,
-                    st,
-                )?;
+                let (leaf_ptr, left_ptr, right_ptr) = {
+                    let internal = noderef_as!(node_ref, Internal);
+                    (
+                        internal.leaf_node.clone(),
+                        internal.left.clone(),
+                        internal.right.clone(),
+                    )
+                };
+
+                let frontier = [
+                    (
+                        NodeID {
+                            path: key,
+                            depth: depth,
+                        },
+                        leaf_ptr,
+                    ),
+                    (
+                        NodeID {
+                            path: key,
+                            depth: depth + 1,
+                        },
+                        left_ptr,
+                    ),
+                    (
+                        NodeID {
+                            path: key,
+                            depth: depth + 1,
+                        },
+                        right_ptr,
+                    ),
+                ];
+                let mut resolved = self.cache.borrow_mut().deref_node_ptrs(ctx, &frontier)?.into_iter();
+                let leaf_node = resolved.next().unwrap();
+                let left_node = resolved.next().unwrap();
+                let right_node = resolved.next().unwrap();
+
+                summary.leaf_node = self._get_path_node(ctx, leaf_node, depth, key, st)?;
+                summary.left = self._get_path_node(ctx, left_node, depth + 1, key, st)?;
+                summary.right = self._get_path_node(ctx, right_node, depth + 1, key, st)?;
 
                 let idx = st.add_summary(&summary)?;
                 return Ok(SubtreePointer {
@@ -0,0 +1,174 @@
+//! Bidirectional Merkle anti-entropy reconciliation between two Urkel
+//! trees that diverged independently, rather than one simply lagging
+//! behind a `sync_root` the other already has.
+//!
+//! Reconciliation walks both trees top-down from their roots, comparing
+//! the hash each side has stored at the current position: identical
+//! hashes mean the whole subtree below is identical, so the walk stops
+//! there without fetching anything further. A mismatch is only followed
+//! into child subtrees while both sides are still internal nodes; the
+//! moment either side bottoms out -- at an actual leaf, or at a missing
+//! subtree (a null pointer) -- the walk stops and the key/value each side
+//! holds there (if any) is read out as a `LeafDiff`, to be applied to
+//! whichever side is missing or stale. This assumes the two replicas
+//! differ only in the content of individual leaves, not in tree shape.
+use std::sync::Arc;
+
+use failure::Fallible;
+use io_context::Context;
+
+use crate::storage::mkvs::urkel::{cache::*, tree::*};
+
+/// A position where two trees disagree, with the key/value each side
+/// holds there -- `None` meaning that side has no leaf at this position
+/// at all.
+#[derive(Clone)]
+pub struct LeafDiff {
+    pub key: Key,
+    pub local: Option<(Hash, Value)>,
+    pub remote: Option<(Hash, Value)>,
+}
+
+impl UrkelTree {
+    /// Reconcile `self` against `other`, returning the minimal set of
+    /// leaves the two disagree on. The walk is a depth-first recursion:
+    /// each side's node at a position is dereferenced (one
+    /// `deref_node_ptr` call per side) only when the recursion reaches
+    /// it, so one child's whole subtree is reconciled before its next
+    /// sibling is fetched. Requests are not batched by tree level.
+    pub fn anti_entropy_diff(
+        &mut self,
+        ctx: &Arc<Context>,
+        other: &mut UrkelTree,
+    ) -> Fallible<Vec<LeafDiff>> {
+        let local_root = self.cache.borrow().get_pending_root();
+        let remote_root = other.cache.borrow().get_pending_root();
+
+        let mut diffs = Vec::new();
+        self._anti_entropy_diff(ctx, other, local_root, remote_root, 0, Key::new(), &mut diffs)?;
+        Ok(diffs)
+    }
+
+    fn _anti_entropy_diff(
+        &mut self,
+        ctx: &Arc<Context>,
+        other: &mut UrkelTree,
+        local_ptr: NodePtrRef,
+        remote_ptr: NodePtrRef,
+        depth: u8,
+        path: Key,
+        diffs: &mut Vec<LeafDiff>,
+    ) -> Fallible<()> {
+        if local_ptr.borrow().hash == remote_ptr.borrow().hash {
+            // Identical subtree (including both being null) -- nothing to
+            // reconcile below this point.
+            return Ok(());
+        }
+
+        let node_id = NodeID {
+            path: &path,
+            depth: depth,
+        };
+        let local_node =
+            self.cache
+                .borrow_mut()
+                .deref_node_ptr(ctx, node_id, local_ptr.clone(), None)?;
+        let remote_node =
+            other
+                .cache
+                .borrow_mut()
+                .deref_node_ptr(ctx, node_id, remote_ptr.clone(), None)?;
+
+        let both_internal = match (&local_node, &remote_node) {
+            (Some(l), Some(r)) => {
+                is_internal_noderef(l) && is_internal_noderef(r)
+            }
+            _ => false,
+        };
+
+        if both_internal {
+            let (local_leaf, local_left, local_right) = {
+                let l = noderef_as!(local_node.as_ref().unwrap(), Internal);
+                (l.leaf_node.clone(), l.left.clone(), l.right.clone())
+            };
+            let (remote_leaf, remote_left, remote_right) = {
+                let r = noderef_as!(remote_node.as_ref().unwrap(), Internal);
+                (r.leaf_node.clone(), r.left.clone(), r.right.clone())
+            };
+
+            self._anti_entropy_diff(
+                ctx,
+                other,
+                local_leaf,
+                remote_leaf,
+                depth,
+                path.clone(),
+                diffs,
+            )?;
+            self._anti_entropy_diff(
+                ctx,
+                other,
+                local_left,
+                remote_left,
+                depth + 1,
+                path.set_bit(depth, false),
+                diffs,
+            )?;
+            self._anti_entropy_diff(
+                ctx,
+                other,
+                local_right,
+                remote_right,
+                depth + 1,
+                path.set_bit(depth, true),
+                diffs,
+            )?;
+            return Ok(());
+        }
+
+        let local_leaf = self.read_leaf_entry(ctx, &local_node)?;
+        let remote_leaf = other.read_leaf_entry(ctx, &remote_node)?;
+        diffs.push(LeafDiff {
+            key: path,
+            local: local_leaf,
+            remote: remote_leaf,
+        });
+        Ok(())
+    }
+
+    /// Read the key/value held at a dereferenced leaf node, if any.
+    fn read_leaf_entry(
+        &mut self,
+        ctx: &Arc<Context>,
+        node: &Option<NodeRef>,
+    ) -> Fallible<Option<(Hash, Value)>> {
+        let node_ref = match node {
+            None => return Ok(None),
+            Some(node_ref) => node_ref,
+        };
+        if !is_leaf_noderef(node_ref) {
+            return Ok(None);
+        }
+
+        let (key, value_ptr) = {
+            let leaf = noderef_as!(node_ref, Leaf);
+            (leaf.key.clone(), leaf.value.clone())
+        };
+        let value = self.cache.borrow_mut().deref_value_ptr(ctx, value_ptr)?;
+        Ok(Some((key, value.unwrap_or_default())))
+    }
+}
+
+fn is_internal_noderef(node_ref: &NodeRef) -> bool {
+    match classify_noderef!(node_ref) {
+        NodeKind::Internal => true,
+        _ => false,
+    }
+}
+
+fn is_leaf_noderef(node_ref: &NodeRef) -> bool {
+    match classify_noderef!(node_ref) {
+        NodeKind::Leaf => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,455 @@
+//! Merkle-pruned diff between two committed roots of the same tree, for
+//! incremental state sync: only the leaves that were inserted, changed,
+//! or deleted between `old_root` and `new_root` are read out, instead of
+//! a replica re-fetching either side's subtree wholesale via
+//! `get_subtree`.
+//!
+//! The traversal is a synchronized dual descent, the same shape as
+//! `anti_entropy_diff`'s reconciliation between two independent replicas:
+//! walk both sides in lockstep from their root pointers, and the moment
+//! a pair of nodes carries the same stored hash, prune -- the subtree
+//! below is provably identical, which is the invariant that makes this
+//! cheap. This assumes `old_root` is still resolvable through the same
+//! backing store as the tree's current root, e.g. because the backend
+//! retains a bounded window of recently committed roots rather than
+//! only the latest one.
+use std::sync::Arc;
+
+use failure::Fallible;
+use io_context::Context;
+
+use crate::storage::mkvs::urkel::{cache::*, tree::*};
+
+/// One leaf that changed between `old_root` and `new_root`. `None`
+/// means the key was deleted; `Some` covers both inserts and value
+/// changes.
+pub type DiffEntry = (Key, Option<Value>);
+
+impl UrkelTree {
+    /// Diff `old_root` against `new_root`, returning every leaf that
+    /// changed between them. See `walk_diff` for the streaming
+    /// equivalent that does not buffer the whole result.
+    pub fn get_diff(
+        &mut self,
+        ctx: &Arc<Context>,
+        old_root: Hash,
+        new_root: Hash,
+    ) -> Fallible<Vec<DiffEntry>> {
+        let mut diffs = Vec::new();
+        self._get_diff(
+            ctx,
+            root_ptr(old_root),
+            root_ptr(new_root),
+            0,
+            Key::new(),
+            &mut diffs,
+        )?;
+        Ok(diffs)
+    }
+
+    /// Streaming counterpart to `get_diff`. Entries are produced
+    /// depth-first as the descent finds them, rather than all at once.
+    pub fn walk_diff<'a>(
+        &'a mut self,
+        ctx: &Arc<Context>,
+        old_root: Hash,
+        new_root: Hash,
+    ) -> DiffWalker<'a> {
+        DiffWalker {
+            tree: self,
+            ctx: ctx.clone(),
+            stack: vec![(root_ptr(old_root), root_ptr(new_root), 0, Key::new())],
+            pending: None,
+        }
+    }
+
+    fn _get_diff(
+        &mut self,
+        ctx: &Arc<Context>,
+        old_ptr: NodePtrRef,
+        new_ptr: NodePtrRef,
+        depth: u8,
+        path: Key,
+        diffs: &mut Vec<DiffEntry>,
+    ) -> Fallible<()> {
+        if old_ptr.borrow().hash == new_ptr.borrow().hash {
+            // Identical subtree (including both being null) -- nothing
+            // changed below this point.
+            return Ok(());
+        }
+
+        let node_id = NodeID {
+            path: &path,
+            depth: depth,
+        };
+        let old_node = self
+            .cache
+            .borrow_mut()
+            .deref_node_ptr(ctx, node_id, old_ptr.clone(), None)?;
+        let new_node = self
+            .cache
+            .borrow_mut()
+            .deref_node_ptr(ctx, node_id, new_ptr.clone(), None)?;
+
+        match (classify_node(&old_node), classify_node(&new_node)) {
+            (NodeKind::Internal, NodeKind::Internal) => {
+                let (old_leaf, old_left, old_right) = {
+                    let internal = noderef_as!(old_node.as_ref().unwrap(), Internal);
+                    (
+                        internal.leaf_node.clone(),
+                        internal.left.clone(),
+                        internal.right.clone(),
+                    )
+                };
+                let (new_leaf, new_left, new_right) = {
+                    let internal = noderef_as!(new_node.as_ref().unwrap(), Internal);
+                    (
+                        internal.leaf_node.clone(),
+                        internal.left.clone(),
+                        internal.right.clone(),
+                    )
+                };
+
+                self._get_diff(ctx, old_leaf, new_leaf, depth, path.clone(), diffs)?;
+                self._get_diff(
+                    ctx,
+                    old_left,
+                    new_left,
+                    depth + 1,
+                    path.set_bit(depth, false),
+                    diffs,
+                )?;
+                self._get_diff(
+                    ctx,
+                    old_right,
+                    new_right,
+                    depth + 1,
+                    path.set_bit(depth, true),
+                    diffs,
+                )?;
+                Ok(())
+            }
+            (NodeKind::Internal, _) => {
+                // The new side bottomed out -- at a leaf, or at a missing
+                // subtree -- while the old side is still a whole
+                // surviving subtree. Flush whatever leaf the new side
+                // holds here as an insert/change, then keep descending
+                // the old side against an always-empty counterpart so
+                // every key it still holds is flushed as a deletion,
+                // instead of being silently dropped.
+                if let Some((new_key, new_value)) = self.read_leaf(ctx, &new_node)? {
+                    diffs.push((new_key, Some(new_value)));
+                }
+                let (old_leaf, old_left, old_right) = {
+                    let internal = noderef_as!(old_node.as_ref().unwrap(), Internal);
+                    (
+                        internal.leaf_node.clone(),
+                        internal.left.clone(),
+                        internal.right.clone(),
+                    )
+                };
+                self._get_diff(ctx, old_leaf, null_ptr(), depth, path.clone(), diffs)?;
+                self._get_diff(
+                    ctx,
+                    old_left,
+                    null_ptr(),
+                    depth + 1,
+                    path.set_bit(depth, false),
+                    diffs,
+                )?;
+                self._get_diff(
+                    ctx,
+                    old_right,
+                    null_ptr(),
+                    depth + 1,
+                    path.set_bit(depth, true),
+                    diffs,
+                )?;
+                Ok(())
+            }
+            (_, NodeKind::Internal) => {
+                // Symmetric case: the old side bottomed out while the
+                // new side is still a whole subtree. Flush the old
+                // side's leaf as a deletion, then descend the new side
+                // against an always-empty counterpart so every key it
+                // holds is flushed as an insert.
+                if let Some((old_key, _)) = self.read_leaf(ctx, &old_node)? {
+                    diffs.push((old_key, None));
+                }
+                let (new_leaf, new_left, new_right) = {
+                    let internal = noderef_as!(new_node.as_ref().unwrap(), Internal);
+                    (
+                        internal.leaf_node.clone(),
+                        internal.left.clone(),
+                        internal.right.clone(),
+                    )
+                };
+                self._get_diff(ctx, null_ptr(), new_leaf, depth, path.clone(), diffs)?;
+                self._get_diff(
+                    ctx,
+                    null_ptr(),
+                    new_left,
+                    depth + 1,
+                    path.set_bit(depth, false),
+                    diffs,
+                )?;
+                self._get_diff(
+                    ctx,
+                    null_ptr(),
+                    new_right,
+                    depth + 1,
+                    path.set_bit(depth, true),
+                    diffs,
+                )?;
+                Ok(())
+            }
+            _ => {
+                // Both sides bottomed out -- at a leaf, or at a missing
+                // subtree. Flush whichever leaf the new side holds here
+                // as an insert/change, and the old side's key as a
+                // deletion if the new side no longer has it.
+                let old_leaf = self.read_leaf(ctx, &old_node)?;
+                let new_leaf = self.read_leaf(ctx, &new_node)?;
+
+                match (old_leaf, new_leaf) {
+                    (Some((old_key, _)), Some((new_key, new_value))) if old_key == new_key => {
+                        diffs.push((new_key, Some(new_value)));
+                    }
+                    (old_leaf, new_leaf) => {
+                        if let Some((old_key, _)) = old_leaf {
+                            diffs.push((old_key, None));
+                        }
+                        if let Some((new_key, new_value)) = new_leaf {
+                            diffs.push((new_key, Some(new_value)));
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Read the key/value held at a dereferenced leaf node, if any,
+    /// mirroring `anti_entropy`'s `read_leaf_entry`.
+    fn read_leaf(&mut self, ctx: &Arc<Context>, node: &Option<NodeRef>) -> Fallible<Option<(Key, Value)>> {
+        let node_ref = match node {
+            None => return Ok(None),
+            Some(node_ref) => node_ref,
+        };
+        if classify_node(&Some(node_ref.clone())) != NodeKind::Leaf {
+            return Ok(None);
+        }
+
+        let (key, value_ptr) = {
+            let leaf = noderef_as!(node_ref, Leaf);
+            (leaf.key.clone(), leaf.value.clone())
+        };
+        let value = self.cache.borrow_mut().deref_value_ptr(ctx, value_ptr)?;
+        Ok(Some((key, value.unwrap_or_default())))
+    }
+}
+
+/// Streaming iterator returned by `UrkelTree::walk_diff`.
+pub struct DiffWalker<'a> {
+    tree: &'a mut UrkelTree,
+    ctx: Arc<Context>,
+    stack: Vec<(NodePtrRef, NodePtrRef, u8, Key)>,
+    /// A leaf-deletion/leaf-insert pair at the same position yields two
+    /// entries at once; the second is parked here until the next
+    /// `next()` call instead of being dropped.
+    pending: Option<DiffEntry>,
+}
+
+impl<'a> Iterator for DiffWalker<'a> {
+    type Item = Fallible<DiffEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(entry) = self.pending.take() {
+            return Some(Ok(entry));
+        }
+
+        loop {
+            let (old_ptr, new_ptr, depth, path) = self.stack.pop()?;
+
+            if old_ptr.borrow().hash == new_ptr.borrow().hash {
+                // Identical subtree -- prune, nothing to yield here.
+                continue;
+            }
+
+            let node_id = NodeID {
+                path: &path,
+                depth: depth,
+            };
+            let old_node = match self
+                .tree
+                .cache
+                .borrow_mut()
+                .deref_node_ptr(&self.ctx, node_id, old_ptr.clone(), None)
+            {
+                Ok(node) => node,
+                Err(error) => return Some(Err(error)),
+            };
+            let new_node = match self
+                .tree
+                .cache
+                .borrow_mut()
+                .deref_node_ptr(&self.ctx, node_id, new_ptr.clone(), None)
+            {
+                Ok(node) => node,
+                Err(error) => return Some(Err(error)),
+            };
+
+            match (classify_node(&old_node), classify_node(&new_node)) {
+                (NodeKind::Internal, NodeKind::Internal) => {
+                    let (old_leaf, old_left, old_right) = {
+                        let internal = noderef_as!(old_node.as_ref().unwrap(), Internal);
+                        (
+                            internal.leaf_node.clone(),
+                            internal.left.clone(),
+                            internal.right.clone(),
+                        )
+                    };
+                    let (new_leaf, new_left, new_right) = {
+                        let internal = noderef_as!(new_node.as_ref().unwrap(), Internal);
+                        (
+                            internal.leaf_node.clone(),
+                            internal.left.clone(),
+                            internal.right.clone(),
+                        )
+                    };
+
+                    let right_path = path.set_bit(depth, true);
+                    let left_path = path.set_bit(depth, false);
+                    self.stack.push((old_right, new_right, depth + 1, right_path));
+                    self.stack.push((old_left, new_left, depth + 1, left_path));
+                    self.stack.push((old_leaf, new_leaf, depth, path));
+                    continue;
+                }
+                (NodeKind::Internal, _) => {
+                    // Same fix as `_get_diff`: don't let the old side's
+                    // surviving subtree get silently dropped just
+                    // because the new side bottomed out here.
+                    let (old_leaf, old_left, old_right) = {
+                        let internal = noderef_as!(old_node.as_ref().unwrap(), Internal);
+                        (
+                            internal.leaf_node.clone(),
+                            internal.left.clone(),
+                            internal.right.clone(),
+                        )
+                    };
+                    let right_path = path.set_bit(depth, true);
+                    let left_path = path.set_bit(depth, false);
+                    self.stack.push((old_right, null_ptr(), depth + 1, right_path));
+                    self.stack.push((old_left, null_ptr(), depth + 1, left_path));
+                    self.stack.push((old_leaf, null_ptr(), depth, path));
+
+                    let new_leaf = match self.tree.read_leaf(&self.ctx, &new_node) {
+                        Ok(leaf) => leaf,
+                        Err(error) => return Some(Err(error)),
+                    };
+                    match new_leaf {
+                        Some((new_key, new_value)) => {
+                            return Some(Ok((new_key, Some(new_value))));
+                        }
+                        None => continue,
+                    }
+                }
+                (_, NodeKind::Internal) => {
+                    let (new_leaf, new_left, new_right) = {
+                        let internal = noderef_as!(new_node.as_ref().unwrap(), Internal);
+                        (
+                            internal.leaf_node.clone(),
+                            internal.left.clone(),
+                            internal.right.clone(),
+                        )
+                    };
+                    let right_path = path.set_bit(depth, true);
+                    let left_path = path.set_bit(depth, false);
+                    self.stack.push((null_ptr(), new_right, depth + 1, right_path));
+                    self.stack.push((null_ptr(), new_left, depth + 1, left_path));
+                    self.stack.push((null_ptr(), new_leaf, depth, path));
+
+                    let old_leaf = match self.tree.read_leaf(&self.ctx, &old_node) {
+                        Ok(leaf) => leaf,
+                        Err(error) => return Some(Err(error)),
+                    };
+                    match old_leaf {
+                        Some((old_key, _)) => return Some(Ok((old_key, None))),
+                        None => continue,
+                    }
+                }
+                _ => {
+                    let old_leaf = match self.tree.read_leaf(&self.ctx, &old_node) {
+                        Ok(leaf) => leaf,
+                        Err(error) => return Some(Err(error)),
+                    };
+                    let new_leaf = match self.tree.read_leaf(&self.ctx, &new_node) {
+                        Ok(leaf) => leaf,
+                        Err(error) => return Some(Err(error)),
+                    };
+
+                    match (old_leaf, new_leaf) {
+                        (Some((old_key, _)), Some((new_key, new_value))) if old_key == new_key => {
+                            return Some(Ok((new_key, Some(new_value))));
+                        }
+                        (old_leaf, new_leaf) => {
+                            // Distinct keys (or one side missing) means
+                            // the old key -- if any -- was deleted and
+                            // the new key -- if any -- was inserted;
+                            // park whichever one doesn't fit in this
+                            // call's return slot.
+                            match (old_leaf, new_leaf) {
+                                (Some((old_key, _)), Some((new_key, new_value))) => {
+                                    self.pending = Some((new_key, Some(new_value)));
+                                    return Some(Ok((old_key, None)));
+                                }
+                                (Some((old_key, _)), None) => {
+                                    return Some(Ok((old_key, None)));
+                                }
+                                (None, Some((new_key, new_value))) => {
+                                    return Some(Ok((new_key, Some(new_value))));
+                                }
+                                (None, None) => continue,
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Construct a standalone, not-yet-resolved node pointer for `hash`, the
+/// same way `ReadSync::get_value`'s `ValuePointer { clean: true, hash:
+/// id, .. }` stub lets a content hash be dereferenced without already
+/// holding a `NodePtrRef` for it.
+fn root_ptr(hash: Hash) -> NodePtrRef {
+    use std::{cell::RefCell, rc::Rc};
+
+    Rc::new(RefCell::new(NodePointer {
+        clean: true,
+        hash: hash,
+        ..Default::default()
+    }))
+}
+
+/// Construct a standalone pointer standing in for "no node here", used
+/// as the counterpart when one side of a dual descent bottoms out (at a
+/// leaf or a missing subtree) while the other is still a whole
+/// surviving internal subtree, so that side's own children can keep
+/// being walked against an always-empty comparison.
+fn null_ptr() -> NodePtrRef {
+    use std::{cell::RefCell, rc::Rc};
+
+    Rc::new(RefCell::new(NodePointer {
+        clean: true,
+        ..Default::default()
+    }))
+}
+
+fn classify_node(node: &Option<NodeRef>) -> NodeKind {
+    match node {
+        None => NodeKind::None,
+        Some(node_ref) => classify_noderef!(node_ref),
+    }
+}
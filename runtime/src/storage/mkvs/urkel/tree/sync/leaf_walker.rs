@@ -0,0 +1,161 @@
+//! A streaming leaf iterator over `UrkelTree`, for callers -- dumps,
+//! full-tree diffs, migrations -- that only want key/value pairs and
+//! would otherwise pay for a `Subtree` built by `get_subtree`/`get_path`
+//! that they immediately throw away again.
+//!
+//! Unlike `_get_subtree`/`_get_path`, which build their result depth-first
+//! via recursion, `LeafWalker` keeps its own explicit stack of
+//! `(NodePtrRef, depth, path)` frames so that it can be driven one leaf
+//! at a time by `Iterator::next` -- suspended between calls -- rather
+//! than requiring the whole traversal to run to completion up front. At
+//! each internal node it pushes the right child, then the left child,
+//! then the embedded `leaf_node` last, so that popping the stack yields
+//! leaves in ascending key order; before descending, it resolves that
+//! frontier of up-to-three siblings with a single batched
+//! `deref_node_ptrs` call, so walking a cold, remote-backed tree costs
+//! one round trip per level instead of one per leaf.
+use std::sync::Arc;
+
+use failure::Fallible;
+use io_context::Context;
+
+use crate::storage::mkvs::urkel::{cache::*, tree::*};
+
+impl UrkelTree {
+    /// Stream every `(key, value)` pair in the tree committed at
+    /// `root_hash`, in ascending key order. Fails lazily: an error
+    /// resolving `root_hash` itself, or any node/value along the way, is
+    /// surfaced as the next `Err` yielded by the iterator rather than
+    /// eagerly at call time.
+    pub fn walk_leaves<'a>(
+        &'a mut self,
+        ctx: &Arc<Context>,
+        root_hash: Hash,
+    ) -> LeafWalker<'a> {
+        let pending_root = self.cache.borrow().get_pending_root();
+        let error = if root_hash != pending_root.borrow().hash {
+            Some(SyncerError::InvalidRoot.into())
+        } else if !pending_root.borrow().clean {
+            Some(SyncerError::DirtyRoot.into())
+        } else {
+            None
+        };
+
+        let stack = if error.is_none() {
+            vec![(pending_root, 0u8, Key::new())]
+        } else {
+            Vec::new()
+        };
+
+        LeafWalker {
+            tree: self,
+            ctx: ctx.clone(),
+            stack,
+            error,
+        }
+    }
+}
+
+/// Iterator returned by `UrkelTree::walk_leaves`. See the module
+/// documentation for the traversal strategy.
+pub struct LeafWalker<'a> {
+    tree: &'a mut UrkelTree,
+    ctx: Arc<Context>,
+    stack: Vec<(NodePtrRef, u8, Key)>,
+    /// Set if `walk_leaves` failed to resolve `root_hash`; surfaced as
+    /// the first item and then cleared, so the iterator ends afterwards.
+    error: Option<Fallible<()>>,
+}
+
+impl<'a> Iterator for LeafWalker<'a> {
+    type Item = Fallible<(Key, Value)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            if let Err(error) = error {
+                return Some(Err(error));
+            }
+        }
+
+        loop {
+            let (ptr, depth, path) = self.stack.pop()?;
+
+            let node_ref = match self.tree.cache.borrow_mut().deref_node_ptr(
+                &self.ctx,
+                NodeID {
+                    path: &path,
+                    depth: depth,
+                },
+                ptr,
+                None,
+            ) {
+                Ok(node_ref) => node_ref,
+                Err(error) => return Some(Err(error)),
+            };
+            let node_ref = match node_ref {
+                None => continue,
+                Some(node_ref) => node_ref,
+            };
+
+            match classify_noderef!(node_ref) {
+                NodeKind::None => unreachable!(),
+                NodeKind::Internal => {
+                    let (leaf_ptr, left_ptr, right_ptr) = {
+                        let internal = noderef_as!(node_ref, Internal);
+                        (
+                            internal.leaf_node.clone(),
+                            internal.left.clone(),
+                            internal.right.clone(),
+                        )
+                    };
+
+                    let leaf_path = path.set_bit(depth, false);
+                    let left_path = path.set_bit(depth, false);
+                    let right_path = path.set_bit(depth, true);
+
+                    let frontier = [
+                        (
+                            NodeID {
+                                path: &leaf_path,
+                                depth: depth,
+                            },
+                            leaf_ptr.clone(),
+                        ),
+                        (
+                            NodeID {
+                                path: &left_path,
+                                depth: depth + 1,
+                            },
+                            left_ptr.clone(),
+                        ),
+                        (
+                            NodeID {
+                                path: &right_path,
+                                depth: depth + 1,
+                            },
+                            right_ptr.clone(),
+                        ),
+                    ];
+                    if let Err(error) = self.tree.cache.borrow_mut().deref_node_ptrs(&self.ctx, &frontier) {
+                        return Some(Err(error));
+                    }
+
+                    self.stack.push((right_ptr, depth + 1, right_path));
+                    self.stack.push((left_ptr, depth + 1, left_path));
+                    self.stack.push((leaf_ptr, depth, leaf_path));
+                    continue;
+                }
+                NodeKind::Leaf => {
+                    let (key, value_ptr) = {
+                        let leaf = noderef_as!(node_ref, Leaf);
+                        (leaf.key.clone(), leaf.value.clone())
+                    };
+                    return match self.tree.cache.borrow_mut().deref_value_ptr(&self.ctx, value_ptr) {
+                        Ok(value) => Some(Ok((key, value.unwrap_or_default()))),
+                        Err(error) => Some(Err(error)),
+                    };
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Stake and escrow backend interface.
+extern crate ekiden_common;
+
+use ekiden_common::bytes::B256;
+use ekiden_common::error::{Error, Result};
+use ekiden_common::futures::{BoxFuture, BoxStream};
+use ekiden_common::uint::U256;
+
+/// Internal amount representation, denominated in base units (i.e. the
+/// smallest indivisible unit of the token, analogous to "wei").
+pub type AmountType = U256;
+
+/// Raise ten to the `decimals`th power, i.e. the number of base units in
+/// one whole token.
+fn decimal_unit(decimals: u8) -> AmountType {
+    let mut unit = AmountType::from(1);
+    for _ in 0..decimals {
+        unit = unit * AmountType::from(10);
+    }
+    unit
+}
+
+/// Parse a human-readable token amount (e.g. `"1.5"`) into base units,
+/// respecting `decimals` digits of fractional precision. Rejects inputs
+/// with more fractional digits than `decimals` allows, so that a
+/// denominated limit can never be silently truncated when scaled up.
+pub fn parse_amount(s: &str, decimals: u8) -> Result<AmountType> {
+    let mut parts = s.splitn(2, '.');
+    let integer_part = parts.next().unwrap_or("");
+    let fractional_part = parts.next().unwrap_or("");
+
+    if fractional_part.len() > decimals as usize {
+        return Err(Error::new(format!(
+            "amount '{}' has more than {} fractional digits",
+            s, decimals
+        )));
+    }
+
+    let integer_value = if integer_part.is_empty() {
+        AmountType::from(0)
+    } else {
+        integer_part
+            .parse::<u64>()
+            .map(AmountType::from)
+            .map_err(|_| Error::new(format!("invalid amount '{}'", s)))?
+    };
+    let fractional_value = if fractional_part.is_empty() {
+        AmountType::from(0)
+    } else {
+        fractional_part
+            .parse::<u64>()
+            .map(AmountType::from)
+            .map_err(|_| Error::new(format!("invalid amount '{}'", s)))?
+    };
+
+    // Scale `fractional_value`, which counts units of
+    // `10^-fractional_part.len()`, up to units of `10^-decimals`.
+    let scale = decimal_unit(decimals - fractional_part.len() as u8);
+
+    Ok(integer_value * decimal_unit(decimals) + fractional_value * scale)
+}
+
+/// Format a base-unit amount as a human-readable token amount (e.g.
+/// `"1.5"`), respecting `decimals` digits of fractional precision.
+/// Trailing fractional zeroes (and a bare trailing `.`) are omitted.
+pub fn format_amount(amount: AmountType, decimals: u8) -> String {
+    if decimals == 0 {
+        return amount.to_string();
+    }
+
+    let unit = decimal_unit(decimals);
+    let integer_part = amount / unit;
+    let fractional_part = amount - integer_part * unit;
+
+    let fractional_str = format!(
+        "{:0>width$}",
+        fractional_part.to_string(),
+        width = decimals as usize
+    );
+    let trimmed = fractional_str.trim_end_matches('0');
+    if trimmed.is_empty() {
+        integer_part.to_string()
+    } else {
+        format!("{}.{}", integer_part, trimmed)
+    }
+}
+
+/// Error codes returned (as the `message` of an `Error`) by a
+/// `StakeEscrowBackend` implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCodes {
+    /// The caller does not hold enough unescrowed balance for the request.
+    InsufficientFunds,
+    /// The caller does not hold enough allowance for the request.
+    InsufficientAllowance,
+    /// The requested escrow does not exist (or has already been released).
+    NoEscrowAccount,
+    /// The requested amount is larger than the escrow's remaining balance.
+    RequestExceedsEscrowedFunds,
+    /// The caller is not the target of the escrow being taken.
+    CallerNotEscrowTarget,
+    /// The caller is not the designated slashing authority.
+    CallerNotSlashingAuthority,
+    /// The backing `StakeEscrowStore` returned inconsistent or unreadable
+    /// data (e.g. a dangling escrow reference).
+    StorageCorrupt,
+    /// `allocate_escrow` would exceed the owner's configured maximum number
+    /// of simultaneously active escrows.
+    TooManyActiveEscrows,
+    /// `allocate_escrow` would escrow more than the owner's configured
+    /// maximum fraction of their `total_stake`.
+    EscrowCapExceeded,
+    /// `transfer`/`transfer_from` would move more than the source
+    /// account's configured `withdrawal_limit`.
+    WithdrawalLimitExceeded,
+}
+
+impl ::std::fmt::Display for ErrorCodes {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let s = match self {
+            &ErrorCodes::InsufficientFunds => "InsufficientFunds",
+            &ErrorCodes::InsufficientAllowance => "InsufficientAllowance",
+            &ErrorCodes::NoEscrowAccount => "NoEscrowAccount",
+            &ErrorCodes::RequestExceedsEscrowedFunds => "RequestExceedsEscrowedFunds",
+            &ErrorCodes::CallerNotEscrowTarget => "CallerNotEscrowTarget",
+            &ErrorCodes::CallerNotSlashingAuthority => "CallerNotSlashingAuthority",
+            &ErrorCodes::StorageCorrupt => "StorageCorrupt",
+            &ErrorCodes::TooManyActiveEscrows => "TooManyActiveEscrows",
+            &ErrorCodes::EscrowCapExceeded => "EscrowCapExceeded",
+            &ErrorCodes::WithdrawalLimitExceeded => "WithdrawalLimitExceeded",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Stake holdings for a single account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StakeStatus {
+    /// Total stake held by the account, including any escrowed amount.
+    pub total_stake: AmountType,
+    /// Portion of `total_stake` that is currently locked up in escrow.
+    pub escrowed: AmountType,
+    /// Remaining amount `owner` could still lock up in escrow before
+    /// hitting its configured maximum escrowable fraction of `total_stake`,
+    /// or `None` if the backend enforces no such cap.
+    pub escrow_headroom: Option<AmountType>,
+    /// Maximum amount, in whole tokens, that a single `transfer`/
+    /// `transfer_from` may move out of this account, or `None` if
+    /// withdrawals are unlimited.
+    pub withdrawal_limit: Option<u64>,
+}
+
+/// Persisted record for a single stake account; an alias of `StakeStatus`
+/// since a `StakeEscrowStore` persists exactly the fields a backend needs
+/// to answer `get_stake_status`.
+pub type StakeAccount = StakeStatus;
+
+/// A single active escrow account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EscrowAccount {
+    /// Unique identifier of this escrow.
+    pub id: B256,
+    /// Owner whose stake is locked up.
+    pub owner: B256,
+    /// Target that may claim the escrowed funds via `take_and_release_escrow`.
+    pub target: B256,
+    /// Amount remaining in the escrow.
+    pub amount: AmountType,
+    /// Auxiliary, caller-supplied identifier (e.g. a contract/batch hash)
+    /// associated with this escrow.
+    pub aux: B256,
+}
+
+/// A notification of a token or escrow state change, published by a
+/// `StakeEscrowBackend` to the subscribers of `watch_events`.
+///
+/// `Transfer`/`Approval`/`Burn` follow ERC20 event semantics so that
+/// downstream tooling (indexers, balance mirrors) can reuse the same
+/// assumptions it would for any ERC20-compatible token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StakeEvent {
+    /// `amount` moved from `from`'s to `to`'s unescrowed balance.
+    Transfer {
+        from: B256,
+        to: B256,
+        amount: AmountType,
+    },
+    /// `owner` granted `spender` an allowance of `amount`.
+    Approval {
+        owner: B256,
+        spender: B256,
+        amount: AmountType,
+    },
+    /// `amount` was permanently destroyed from `from`'s balance.
+    Burn { from: B256, amount: AmountType },
+    /// A new escrow `id` was allocated by `owner` for `target`.
+    EscrowCreated {
+        id: B256,
+        owner: B256,
+        target: B256,
+        amount: AmountType,
+        aux: B256,
+    },
+    /// `taken` was released from escrow `id` to `target`, leaving
+    /// `remaining` still escrowed (zero if the escrow was released).
+    EscrowTaken {
+        id: B256,
+        target: B256,
+        taken: AmountType,
+        remaining: AmountType,
+    },
+}
+
+/// A cursor over an owner's active escrow accounts.
+///
+/// This is returned by `list_active_escrows_iterator` and threaded back
+/// through `list_active_escrows_get` so that a backend is never required
+/// to hand out a live reference into its internal storage.
+#[derive(Clone, Debug)]
+pub struct EscrowAccountIterator {
+    /// Owner whose escrows are being listed.
+    pub owner: B256,
+    /// Index of the next escrow to return.
+    pub position: usize,
+    /// Whether a further call to `list_active_escrows_get` will succeed.
+    pub has_next: bool,
+}
+
+/// Stake holdings, ERC20-style token transfer, and escrow operations.
+///
+/// Escrow lets an owner lock up a portion of their stake for a target to
+/// later claim (`take_and_release_escrow`) or for a slashing authority to
+/// burn in response to misbehavior (`slash_escrow`).
+pub trait StakeEscrowBackend: Sync + Send {
+    /// Name of the token (e.g. "EkidenStake").
+    fn get_name(&self) -> BoxFuture<String>;
+
+    /// Ticker symbol of the token (e.g. "E$").
+    fn get_symbol(&self) -> BoxFuture<String>;
+
+    /// Number of decimals used to denominate the token's smallest unit.
+    fn get_decimals(&self) -> BoxFuture<u8>;
+
+    /// Total number of base units in existence.
+    fn get_total_supply(&self) -> BoxFuture<AmountType>;
+
+    /// Stake status (total stake and amount escrowed) of `owner`.
+    fn get_stake_status(&self, owner: B256) -> BoxFuture<StakeStatus>;
+
+    /// Unescrowed balance of `owner`, i.e. `total_stake - escrowed`.
+    fn balance_of(&self, owner: B256) -> BoxFuture<AmountType>;
+
+    /// Transfer `amount` from `from` to `to`'s unescrowed balance.
+    fn transfer(&self, from: B256, to: B256, amount: AmountType) -> BoxFuture<bool>;
+
+    /// Transfer `amount` from `src` to `dst` on `src`'s behalf, debiting
+    /// the allowance `src` has granted to `sender`.
+    fn transfer_from(
+        &self,
+        sender: B256,
+        src: B256,
+        dst: B256,
+        amount: AmountType,
+    ) -> BoxFuture<bool>;
+
+    /// Approve `spender` to transfer/burn up to `amount` on `owner`'s behalf.
+    fn approve(&self, owner: B256, spender: B256, amount: AmountType) -> BoxFuture<bool>;
+
+    /// Remaining allowance `spender` has over `owner`'s balance.
+    fn allowance(&self, owner: B256, spender: B256) -> BoxFuture<AmountType>;
+
+    /// Permanently destroy `amount` of `owner`'s unescrowed balance,
+    /// reducing `get_total_supply`.
+    fn burn(&self, owner: B256, amount: AmountType) -> BoxFuture<bool>;
+
+    /// Burn `amount` from `owner` on `owner`'s behalf, debiting the
+    /// allowance `owner` has granted to `caller`.
+    fn burn_from(&self, caller: B256, owner: B256, amount: AmountType) -> BoxFuture<bool>;
+
+    /// Lock up `amount` of `owner`'s unescrowed balance in a new escrow
+    /// that `target` may later claim, returning the new escrow's id.
+    fn allocate_escrow(
+        &self,
+        owner: B256,
+        target: B256,
+        amount: AmountType,
+        aux: B256,
+    ) -> BoxFuture<B256>;
+
+    /// Begin iterating over `owner`'s active escrows.
+    fn list_active_escrows_iterator(&self, owner: B256) -> BoxFuture<EscrowAccountIterator>;
+
+    /// Fetch the next escrow from an iterator previously obtained from
+    /// `list_active_escrows_iterator`, returning it along with the
+    /// advanced iterator.
+    fn list_active_escrows_get(
+        &self,
+        iter: EscrowAccountIterator,
+    ) -> BoxFuture<(EscrowAccount, EscrowAccountIterator)>;
+
+    /// Look up a single escrow by id.
+    fn fetch_escrow_by_id(&self, id: B256) -> BoxFuture<EscrowAccount>;
+
+    /// Claim up to `amount_requested` from the escrow `id`, paying it to
+    /// the escrow's target. Only the escrow's target may call this. The
+    /// escrow is released (deleted) if this empties it, otherwise it is
+    /// left open with its remaining balance.
+    fn take_and_release_escrow(
+        &self,
+        target: B256,
+        id: B256,
+        amount_requested: AmountType,
+    ) -> BoxFuture<AmountType>;
+
+    /// Slash `amount` out of the escrow `id` in response to misbehavior by
+    /// its owner. Unlike `take_and_release_escrow`, the slashed amount is
+    /// burned (or routed to a reward pool) rather than paid to the escrow's
+    /// target. Only the backend's designated slashing authority may call
+    /// this. The escrow is released if this empties it, otherwise it is
+    /// left open with its remaining balance.
+    fn slash_escrow(&self, caller: B256, id: B256, amount: AmountType) -> BoxFuture<AmountType>;
+
+    /// Subscribe to a live stream of `StakeEvent`s published by the token
+    /// and escrow methods on this backend (`transfer`, `transfer_from`,
+    /// `approve`, `burn`, `burn_from`, `allocate_escrow`,
+    /// `take_and_release_escrow` and `slash_escrow`), in the order they
+    /// were applied. `set_withdrawal_limit` publishes no event.
+    fn watch_events(&self) -> BoxStream<StakeEvent>;
+
+    /// Total inflationary staking rewards credited to `owner` so far.
+    ///
+    /// Returns zero for a backend that does not implement epoch-driven
+    /// inflation.
+    fn get_accumulated_rewards(&self, owner: B256) -> BoxFuture<AmountType>;
+
+    /// Set (or clear, with `None`) the maximum amount, in whole tokens,
+    /// that a single `transfer`/`transfer_from` may move out of `owner`.
+    fn set_withdrawal_limit(&self, owner: B256, limit: Option<u64>) -> BoxFuture<()>;
+}
+
+/// Pluggable persistence layer for a `StakeEscrowBackend`.
+///
+/// This abstracts away *how* accounts, escrows, and allowances are stored
+/// so that the ERC20/escrow logic in a backend can run unmodified over an
+/// in-memory map (for tests), an embedded key-value store, or a remote
+/// database. Every operation returns a `Result` so that a backend can
+/// surface a storage failure as `ErrorCodes::StorageCorrupt` rather than
+/// panicking, the way trie-backed ledger state threads database errors
+/// through `balance()`/`root()`.
+pub trait StakeEscrowStore: Sync + Send {
+    /// Look up a stake account, if one has ever been created for `id`.
+    fn get_stake_account(&self, id: &B256) -> Result<Option<StakeAccount>>;
+
+    /// Create or update the stake account for `id`.
+    fn put_stake_account(&self, id: B256, account: StakeAccount) -> Result<()>;
+
+    /// Look up an escrow account by id.
+    fn get_escrow_account(&self, id: &B256) -> Result<Option<EscrowAccount>>;
+
+    /// Create or update an escrow account.
+    fn put_escrow_account(&self, id: B256, account: EscrowAccount) -> Result<()>;
+
+    /// Remove an escrow account once it has been fully released or slashed.
+    fn delete_escrow_account(&self, id: &B256) -> Result<()>;
+
+    /// Look up the allowance `owner` has granted to `spender`, if any.
+    fn get_allowance(&self, owner: &B256, spender: &B256) -> Result<Option<AmountType>>;
+
+    /// Set the allowance `owner` has granted to `spender`.
+    fn put_allowance(&self, owner: B256, spender: B256, amount: AmountType) -> Result<()>;
+
+    /// List the ids of `owner`'s currently active escrows, in the order
+    /// they should be walked by `list_active_escrows_iterator`.
+    fn list_active_escrow_ids(&self, owner: &B256) -> Result<Vec<B256>>;
+
+    /// Record that `id` is now one of `owner`'s active escrows.
+    fn add_active_escrow_id(&self, owner: B256, id: B256) -> Result<()>;
+
+    /// Remove `id` from `owner`'s active escrow list.
+    fn remove_active_escrow_id(&self, owner: &B256, id: &B256) -> Result<()>;
+
+    /// List the owners of every stake account that currently has a
+    /// non-zero escrowed balance. Used to weight proportional reward
+    /// distribution across epochs.
+    fn list_escrowed_accounts(&self) -> Result<Vec<B256>>;
+
+    /// Run `f` with exclusive access to the store held for its entire
+    /// duration, so a caller's multi-step check-then-write sequence (e.g.
+    /// checking an allowance or an escrowed balance, then debiting it) is
+    /// atomic with respect to every other `StakeEscrowStore` call, rather
+    /// than racing across the independent per-method locks each of the
+    /// calls above takes on its own. `f` is handed a store to operate on
+    /// in place of `self` -- calling back into `self` from inside `f`
+    /// would re-enter the same lock and deadlock.
+    fn transaction(&self, f: &mut FnMut(&StakeEscrowStore) -> Result<()>) -> Result<()>;
+}
@@ -0,0 +1,790 @@
+//! A dummy, in-process `StakeEscrowBackend` implementation.
+extern crate ekiden_beacon_base;
+extern crate ekiden_common;
+extern crate ekiden_stake_base;
+
+#[macro_use]
+extern crate log;
+
+mod store;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ekiden_beacon_base::RandomBeacon;
+use ekiden_common::bytes::B256;
+use ekiden_common::error::{Error, Result};
+use ekiden_common::futures::cpupool::CpuPool;
+use ekiden_common::futures::{future, BoxFuture, BoxStream, Stream};
+use ekiden_common::subscribers::StreamSubscribers;
+use ekiden_common::uint::U256;
+
+pub use ekiden_stake_base::*;
+
+pub use store::MapStakeEscrowStore;
+
+struct Meta {
+    name: String,
+    symbol: String,
+    decimals: u8,
+    total_supply: AmountType,
+
+    /// Account that is allowed to mint tokens by transferring from itself
+    /// (its own stake is the "faucet"), mirroring an ERC20 owner account.
+    oasis: B256,
+    /// Account that is allowed to call `slash_escrow`.
+    slashing_authority: B256,
+
+    next_escrow_id: U256,
+
+    /// Per-epoch inflation rate, as a `(numerator, denominator)` fraction
+    /// of `total_supply` to mint each epoch. `None` disables inflation.
+    inflation_rate: Option<(u64, u64)>,
+    /// Remainder left over from the last epoch's integer-division reward
+    /// split, carried forward so the sum of credited rewards exactly
+    /// equals minted supply over time.
+    reward_dust: AmountType,
+
+    /// Maximum number of escrows an owner may have active at once.
+    /// `None` leaves the active-escrow list unbounded.
+    max_active_escrows: Option<usize>,
+    /// Maximum `(numerator, denominator)` fraction of an owner's
+    /// `total_stake` that may be escrowed at once. `None` leaves the
+    /// escrowed fraction unbounded.
+    max_escrowed_fraction: Option<(u64, u64)>,
+}
+
+impl Meta {
+    fn gen_escrow_id(&mut self) -> B256 {
+        let id = B256::from_slice(&self.next_escrow_id.to_vec());
+        self.next_escrow_id = self.next_escrow_id + U256::from(1);
+        id
+    }
+}
+
+/// An in-process `StakeEscrowBackend` that keeps all state behind a
+/// pluggable `StakeEscrowStore`.
+///
+/// By default it runs over `MapStakeEscrowStore`, an in-memory map, so
+/// state does not survive a process restart; any other `StakeEscrowStore`
+/// implementation (e.g. a persistent key-value backend) can be substituted
+/// without touching the ERC20/escrow logic below.
+pub struct DummyStakeEscrowBackend {
+    store: Box<StakeEscrowStore>,
+    meta: Mutex<Meta>,
+    events: StreamSubscribers<StakeEvent>,
+    /// Accumulated inflationary rewards credited to each owner so far.
+    rewards: Mutex<HashMap<B256, AmountType>>,
+}
+
+/// Fetch a stake account from `store`, defaulting to a zeroed account if
+/// none has been created yet for `id`.
+fn get_account(store: &StakeEscrowStore, id: B256) -> Result<StakeAccount> {
+    Ok(store.get_stake_account(&id)?.unwrap_or(StakeAccount {
+        total_stake: AmountType::from(0),
+        escrowed: AmountType::from(0),
+        escrow_headroom: None,
+        withdrawal_limit: None,
+    }))
+}
+
+/// Fetch an escrow account from `store`, turning a missing entry into the
+/// usual `NoEscrowAccount` error rather than a storage-layer `None`.
+fn get_escrow(store: &StakeEscrowStore, id: &B256) -> Result<EscrowAccount> {
+    store
+        .get_escrow_account(id)?
+        .ok_or_else(|| Error::new(ErrorCodes::NoEscrowAccount.to_string()))
+}
+
+/// Remaining balance of escrow `id`, or zero if it was released entirely.
+fn remaining_escrow_amount(store: &StakeEscrowStore, id: &B256) -> AmountType {
+    store
+        .get_escrow_account(id)
+        .ok()
+        .and_then(|eas| eas)
+        .map(|eas| eas.amount)
+        .unwrap_or(AmountType::from(0))
+}
+
+fn get_allowance(store: &StakeEscrowStore, owner: &B256, spender: &B256) -> Result<AmountType> {
+    Ok(store
+        .get_allowance(owner, spender)?
+        .unwrap_or(AmountType::from(0)))
+}
+
+/// Reject `amount` if it exceeds `account`'s configured `withdrawal_limit`,
+/// scaling the limit (given in whole tokens) up to base units by
+/// `decimals` before comparing -- the limit must never be compared against
+/// `amount` while still denominated in whole tokens.
+fn check_withdrawal_limit(account: &StakeAccount, amount: AmountType, decimals: u8) -> Result<()> {
+    if let Some(limit) = account.withdrawal_limit {
+        let mut limit_base_units = AmountType::from(limit);
+        for _ in 0..decimals {
+            limit_base_units = limit_base_units * AmountType::from(10);
+        }
+        if amount > limit_base_units {
+            return Err(Error::new(ErrorCodes::WithdrawalLimitExceeded.to_string()));
+        }
+    }
+    Ok(())
+}
+
+impl DummyStakeEscrowBackend {
+    /// Construct a new backend over a fresh `MapStakeEscrowStore`.
+    ///
+    /// `oasis` is credited with the entire `initial_total_tokens` supply
+    /// and also doubles as the authority permitted to call `slash_escrow`.
+    /// `initial_total_tokens` is given in whole tokens and is scaled up by
+    /// `10^18` (i.e. 18 decimals) to obtain the base-unit total supply.
+    pub fn new(
+        oasis: B256,
+        name: String,
+        symbol: String,
+        initial_total_tokens: AmountType,
+    ) -> Self {
+        Self::new_with_slashing_authority(oasis, oasis, name, symbol, initial_total_tokens)
+    }
+
+    /// As `new`, but with a slashing authority distinct from `oasis`.
+    pub fn new_with_slashing_authority(
+        oasis: B256,
+        slashing_authority: B256,
+        name: String,
+        symbol: String,
+        initial_total_tokens: AmountType,
+    ) -> Self {
+        Self::new_with_store(
+            oasis,
+            slashing_authority,
+            name,
+            symbol,
+            initial_total_tokens,
+            None,
+            None,
+            None,
+            Box::new(MapStakeEscrowStore::new()),
+        )
+    }
+
+    /// As `new_with_slashing_authority`, but with a configured per-epoch
+    /// inflation rate (see `notify_new_epoch`).
+    pub fn new_with_inflation(
+        oasis: B256,
+        slashing_authority: B256,
+        name: String,
+        symbol: String,
+        initial_total_tokens: AmountType,
+        inflation_rate: (u64, u64),
+    ) -> Self {
+        Self::new_with_store(
+            oasis,
+            slashing_authority,
+            name,
+            symbol,
+            initial_total_tokens,
+            Some(inflation_rate),
+            None,
+            None,
+            Box::new(MapStakeEscrowStore::new()),
+        )
+    }
+
+    /// As `new_with_slashing_authority`, but with configured caps on an
+    /// owner's number of simultaneously active escrows and the fraction of
+    /// their `total_stake` that may be escrowed at once. Either limit may
+    /// be omitted by passing `None`.
+    pub fn new_with_limits(
+        oasis: B256,
+        slashing_authority: B256,
+        name: String,
+        symbol: String,
+        initial_total_tokens: AmountType,
+        max_active_escrows: Option<usize>,
+        max_escrowed_fraction: Option<(u64, u64)>,
+    ) -> Self {
+        Self::new_with_store(
+            oasis,
+            slashing_authority,
+            name,
+            symbol,
+            initial_total_tokens,
+            None,
+            max_active_escrows,
+            max_escrowed_fraction,
+            Box::new(MapStakeEscrowStore::new()),
+        )
+    }
+
+    /// As `new_with_slashing_authority`, but over a caller-supplied store
+    /// and with an optional per-epoch inflation rate and optional caps on
+    /// an owner's active escrows (see `new_with_limits`).
+    pub fn new_with_store(
+        oasis: B256,
+        slashing_authority: B256,
+        name: String,
+        symbol: String,
+        initial_total_tokens: AmountType,
+        inflation_rate: Option<(u64, u64)>,
+        max_active_escrows: Option<usize>,
+        max_escrowed_fraction: Option<(u64, u64)>,
+        store: Box<StakeEscrowStore>,
+    ) -> Self {
+        let decimals = 18u8;
+        let total_supply = initial_total_tokens * AmountType::from(1_000_000_000_000_000_000u64);
+
+        store
+            .put_stake_account(
+                oasis,
+                StakeAccount {
+                    total_stake: total_supply,
+                    escrowed: AmountType::from(0),
+                    escrow_headroom: None,
+                    withdrawal_limit: None,
+                },
+            )
+            .expect("initial stake account store to in-memory/fresh store cannot fail");
+
+        Self {
+            store,
+            meta: Mutex::new(Meta {
+                name,
+                symbol,
+                decimals,
+                total_supply,
+                oasis,
+                slashing_authority,
+                next_escrow_id: U256::from(1),
+                inflation_rate,
+                reward_dust: AmountType::from(0),
+                max_active_escrows,
+                max_escrowed_fraction,
+            }),
+            events: StreamSubscribers::new(),
+            rewards: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Mint this epoch's inflationary reward (if an inflation rate is
+    /// configured) and distribute it across every account with an
+    /// outstanding escrowed balance, proportionally to that balance.
+    ///
+    /// Minted amount = `total_supply * numerator / denominator`, using
+    /// integer arithmetic throughout. Each owner's share is similarly
+    /// truncated; the truncated remainder ("dust") left over from this
+    /// epoch's split is carried into next epoch's pool rather than
+    /// dropped, so that rewards credited over time sum exactly to the
+    /// supply minted over time. If nothing is currently escrowed, the
+    /// whole mint is credited to the `oasis` account instead of being
+    /// silently lost.
+    pub fn notify_new_epoch(&self) -> Result<()> {
+        let (numerator, denominator) = {
+            let meta = self.meta.lock().unwrap();
+            match meta.inflation_rate {
+                Some(rate) => rate,
+                None => return Ok(()),
+            }
+        };
+
+        let owners = self.store.list_escrowed_accounts()?;
+        let mut total_escrowed = AmountType::from(0);
+        let mut weights = Vec::with_capacity(owners.len());
+        for owner in owners {
+            let account = get_account(&*self.store, owner)?;
+            if account.escrowed > AmountType::from(0) {
+                total_escrowed = total_escrowed + account.escrowed;
+                weights.push((owner, account.escrowed));
+            }
+        }
+
+        let mut meta = self.meta.lock().unwrap();
+        let minted = meta.total_supply * AmountType::from(numerator) / AmountType::from(denominator);
+        if minted == AmountType::from(0) {
+            return Ok(());
+        }
+
+        if total_escrowed == AmountType::from(0) {
+            let oasis = meta.oasis;
+            meta.total_supply = meta.total_supply + minted;
+            drop(meta);
+
+            let mut oasis_account = get_account(&*self.store, oasis)?;
+            oasis_account.total_stake = oasis_account.total_stake + minted;
+            self.store.put_stake_account(oasis, oasis_account)?;
+            self.credit_reward(oasis, minted);
+            return Ok(());
+        }
+
+        let pool = minted + meta.reward_dust;
+        meta.total_supply = meta.total_supply + minted;
+
+        let mut distributed = AmountType::from(0);
+        for (owner, weight) in weights {
+            let share = pool * weight / total_escrowed;
+            if share == AmountType::from(0) {
+                continue;
+            }
+
+            let mut account = get_account(&*self.store, owner)?;
+            account.total_stake = account.total_stake + share;
+            self.store.put_stake_account(owner, account)?;
+            self.credit_reward(owner, share);
+
+            distributed = distributed + share;
+        }
+
+        meta.reward_dust = pool - distributed;
+
+        Ok(())
+    }
+
+    fn credit_reward(&self, owner: B256, amount: AmountType) {
+        let mut rewards = self.rewards.lock().unwrap();
+        let entry = rewards.entry(owner).or_insert(AmountType::from(0));
+        *entry = *entry + amount;
+    }
+}
+
+/// Subscribe `backend` to `beacon`'s epoch transitions on `executor`,
+/// minting and distributing inflationary staking rewards at the start of
+/// every new epoch.
+///
+/// This is the simplest way to wire the beacon's `watch_beacons` epoch
+/// signal into the stake backend; a node's startup code calls it once
+/// after constructing both the backend and the beacon.
+pub fn drive_epochs(
+    backend: Arc<DummyStakeEscrowBackend>,
+    beacon: Arc<RandomBeacon>,
+    executor: &mut CpuPool,
+) {
+    executor.spawn(beacon.watch_beacons().for_each(move |_epoch_and_entropy| {
+        if let Err(error) = backend.notify_new_epoch() {
+            error!(
+                "Failed to process epoch for staking rewards: {}",
+                error.message
+            );
+        }
+        future::ok(())
+    }));
+}
+
+impl StakeEscrowBackend for DummyStakeEscrowBackend {
+    fn get_name(&self) -> BoxFuture<String> {
+        Box::new(future::ok(self.meta.lock().unwrap().name.clone()))
+    }
+
+    fn get_symbol(&self) -> BoxFuture<String> {
+        Box::new(future::ok(self.meta.lock().unwrap().symbol.clone()))
+    }
+
+    fn get_decimals(&self) -> BoxFuture<u8> {
+        Box::new(future::ok(self.meta.lock().unwrap().decimals))
+    }
+
+    fn get_total_supply(&self) -> BoxFuture<AmountType> {
+        Box::new(future::ok(self.meta.lock().unwrap().total_supply))
+    }
+
+    fn get_stake_status(&self, owner: B256) -> BoxFuture<StakeStatus> {
+        let result = (|| -> Result<StakeStatus> {
+            let account = get_account(&*self.store, owner)?;
+            let escrow_headroom = self
+                .meta
+                .lock()
+                .unwrap()
+                .max_escrowed_fraction
+                .map(|(numerator, denominator)| {
+                    let max_escrowed = account.total_stake * AmountType::from(numerator)
+                        / AmountType::from(denominator);
+                    if max_escrowed > account.escrowed {
+                        max_escrowed - account.escrowed
+                    } else {
+                        AmountType::from(0)
+                    }
+                });
+            Ok(StakeStatus {
+                total_stake: account.total_stake,
+                escrowed: account.escrowed,
+                escrow_headroom,
+                withdrawal_limit: account.withdrawal_limit,
+            })
+        })();
+        Box::new(future::result(result))
+    }
+
+    fn balance_of(&self, owner: B256) -> BoxFuture<AmountType> {
+        let result = (|| -> Result<AmountType> {
+            let account = get_account(&*self.store, owner)?;
+            Ok(account.total_stake - account.escrowed)
+        })();
+        Box::new(future::result(result))
+    }
+
+    fn transfer(&self, from: B256, to: B256, amount: AmountType) -> BoxFuture<bool> {
+        let decimals = self.meta.lock().unwrap().decimals;
+        let result = self.store.transaction(&mut |store| {
+            let mut from_account = get_account(store, from)?;
+            if from_account.total_stake - from_account.escrowed < amount {
+                return Err(Error::new(ErrorCodes::InsufficientFunds.to_string()));
+            }
+            check_withdrawal_limit(&from_account, amount, decimals)?;
+            from_account.total_stake = from_account.total_stake - amount;
+            store.put_stake_account(from, from_account)?;
+
+            let mut to_account = get_account(store, to)?;
+            to_account.total_stake = to_account.total_stake + amount;
+            store.put_stake_account(to, to_account)?;
+
+            Ok(())
+        }).map(|_| true);
+        if result.is_ok() {
+            self.events.notify(&StakeEvent::Transfer { from, to, amount });
+        }
+        Box::new(future::result(result))
+    }
+
+    fn transfer_from(
+        &self,
+        sender: B256,
+        src: B256,
+        dst: B256,
+        amount: AmountType,
+    ) -> BoxFuture<bool> {
+        let decimals = self.meta.lock().unwrap().decimals;
+        let result = self.store.transaction(&mut |store| {
+            let allowance = get_allowance(store, &src, &sender)?;
+            if allowance < amount {
+                return Err(Error::new(ErrorCodes::InsufficientAllowance.to_string()));
+            }
+
+            let mut src_account = get_account(store, src)?;
+            if src_account.total_stake - src_account.escrowed < amount {
+                return Err(Error::new(ErrorCodes::InsufficientFunds.to_string()));
+            }
+            check_withdrawal_limit(&src_account, amount, decimals)?;
+            src_account.total_stake = src_account.total_stake - amount;
+            store.put_stake_account(src, src_account)?;
+
+            let mut dst_account = get_account(store, dst)?;
+            dst_account.total_stake = dst_account.total_stake + amount;
+            store.put_stake_account(dst, dst_account)?;
+
+            store.put_allowance(src, sender, allowance - amount)?;
+
+            Ok(())
+        }).map(|_| true);
+        if result.is_ok() {
+            self.events.notify(&StakeEvent::Transfer {
+                from: src,
+                to: dst,
+                amount,
+            });
+        }
+        Box::new(future::result(result))
+    }
+
+    fn approve(&self, owner: B256, spender: B256, amount: AmountType) -> BoxFuture<bool> {
+        let result = self
+            .store
+            .put_allowance(owner, spender, amount)
+            .map(|_| true);
+        if result.is_ok() {
+            self.events.notify(&StakeEvent::Approval {
+                owner,
+                spender,
+                amount,
+            });
+        }
+        Box::new(future::result(result))
+    }
+
+    fn allowance(&self, owner: B256, spender: B256) -> BoxFuture<AmountType> {
+        let result = get_allowance(&*self.store, &owner, &spender);
+        Box::new(future::result(result))
+    }
+
+    fn burn(&self, owner: B256, amount: AmountType) -> BoxFuture<bool> {
+        let result = self
+            .store
+            .transaction(&mut |store| {
+                let mut account = get_account(store, owner)?;
+                if account.total_stake - account.escrowed < amount {
+                    return Err(Error::new(ErrorCodes::InsufficientFunds.to_string()));
+                }
+                account.total_stake = account.total_stake - amount;
+                store.put_stake_account(owner, account)
+            })
+            .map(|_| {
+                let mut meta = self.meta.lock().unwrap();
+                meta.total_supply = meta.total_supply - amount;
+                true
+            });
+        if result.is_ok() {
+            self.events.notify(&StakeEvent::Burn { from: owner, amount });
+        }
+        Box::new(future::result(result))
+    }
+
+    fn burn_from(&self, caller: B256, owner: B256, amount: AmountType) -> BoxFuture<bool> {
+        let result = self
+            .store
+            .transaction(&mut |store| {
+                let allowance = get_allowance(store, &owner, &caller)?;
+                if allowance < amount {
+                    return Err(Error::new(ErrorCodes::InsufficientAllowance.to_string()));
+                }
+
+                let mut account = get_account(store, owner)?;
+                if account.total_stake - account.escrowed < amount {
+                    return Err(Error::new(ErrorCodes::InsufficientFunds.to_string()));
+                }
+                account.total_stake = account.total_stake - amount;
+                store.put_stake_account(owner, account)?;
+
+                store.put_allowance(owner, caller, allowance - amount)
+            })
+            .map(|_| {
+                let mut meta = self.meta.lock().unwrap();
+                meta.total_supply = meta.total_supply - amount;
+                true
+            });
+        if result.is_ok() {
+            self.events.notify(&StakeEvent::Burn { from: owner, amount });
+        }
+        Box::new(future::result(result))
+    }
+
+    fn allocate_escrow(
+        &self,
+        owner: B256,
+        target: B256,
+        amount: AmountType,
+        aux: B256,
+    ) -> BoxFuture<B256> {
+        let (max_active_escrows, max_escrowed_fraction) = {
+            let meta = self.meta.lock().unwrap();
+            (meta.max_active_escrows, meta.max_escrowed_fraction)
+        };
+        let mut new_id = None;
+        let result = self
+            .store
+            .transaction(&mut |store| {
+                let mut account = get_account(store, owner)?;
+                if account.total_stake - account.escrowed < amount {
+                    return Err(Error::new(ErrorCodes::InsufficientFunds.to_string()));
+                }
+
+                if let Some(max_active_escrows) = max_active_escrows {
+                    if store.list_active_escrow_ids(&owner)?.len() >= max_active_escrows {
+                        return Err(Error::new(ErrorCodes::TooManyActiveEscrows.to_string()));
+                    }
+                }
+                if let Some((numerator, denominator)) = max_escrowed_fraction {
+                    let max_escrowed = account.total_stake * AmountType::from(numerator)
+                        / AmountType::from(denominator);
+                    if account.escrowed + amount > max_escrowed {
+                        return Err(Error::new(ErrorCodes::EscrowCapExceeded.to_string()));
+                    }
+                }
+
+                account.escrowed = account.escrowed + amount;
+                store.put_stake_account(owner, account)?;
+
+                let id = self.meta.lock().unwrap().gen_escrow_id();
+                store.put_escrow_account(
+                    id,
+                    EscrowAccount {
+                        id,
+                        owner,
+                        target,
+                        amount,
+                        aux,
+                    },
+                )?;
+                store.add_active_escrow_id(owner, id)?;
+
+                new_id = Some(id);
+                Ok(())
+            })
+            .map(|_| new_id.unwrap());
+        if let Ok(id) = &result {
+            self.events.notify(&StakeEvent::EscrowCreated {
+                id: *id,
+                owner,
+                target,
+                amount,
+                aux,
+            });
+        }
+        Box::new(future::result(result))
+    }
+
+    fn list_active_escrows_iterator(&self, owner: B256) -> BoxFuture<EscrowAccountIterator> {
+        let result = (|| -> Result<EscrowAccountIterator> {
+            let has_next = !self.store.list_active_escrow_ids(&owner)?.is_empty();
+            Ok(EscrowAccountIterator {
+                owner,
+                position: 0,
+                has_next,
+            })
+        })();
+        Box::new(future::result(result))
+    }
+
+    fn list_active_escrows_get(
+        &self,
+        iter: EscrowAccountIterator,
+    ) -> BoxFuture<(EscrowAccount, EscrowAccountIterator)> {
+        let result = (|| -> Result<(EscrowAccount, EscrowAccountIterator)> {
+            let ids = self.store.list_active_escrow_ids(&iter.owner)?;
+            let id = ids
+                .get(iter.position)
+                .ok_or_else(|| Error::new(ErrorCodes::NoEscrowAccount.to_string()))?;
+            // The active-escrow list and the escrow account map must agree;
+            // a dangling reference here means the store is corrupt.
+            let eas = self
+                .store
+                .get_escrow_account(id)?
+                .ok_or_else(|| Error::new(ErrorCodes::StorageCorrupt.to_string()))?;
+
+            let next_position = iter.position + 1;
+            let next_iter = EscrowAccountIterator {
+                owner: iter.owner,
+                position: next_position,
+                has_next: next_position < ids.len(),
+            };
+
+            Ok((eas, next_iter))
+        })();
+        Box::new(future::result(result))
+    }
+
+    fn fetch_escrow_by_id(&self, id: B256) -> BoxFuture<EscrowAccount> {
+        let result = get_escrow(&*self.store, &id);
+        Box::new(future::result(result))
+    }
+
+    fn take_and_release_escrow(
+        &self,
+        target: B256,
+        id: B256,
+        amount_requested: AmountType,
+    ) -> BoxFuture<AmountType> {
+        let result = self
+            .store
+            .transaction(&mut |store| {
+                let mut eas = get_escrow(store, &id)?;
+
+                if eas.target != target {
+                    return Err(Error::new(ErrorCodes::CallerNotEscrowTarget.to_string()));
+                }
+                if amount_requested > eas.amount {
+                    return Err(Error::new(
+                        ErrorCodes::RequestExceedsEscrowedFunds.to_string(),
+                    ));
+                }
+
+                let mut owner_account = get_account(store, eas.owner)?;
+                owner_account.total_stake = owner_account.total_stake - amount_requested;
+                owner_account.escrowed = owner_account.escrowed - amount_requested;
+                store.put_stake_account(eas.owner, owner_account)?;
+
+                let mut target_account = get_account(store, target)?;
+                target_account.total_stake = target_account.total_stake + amount_requested;
+                store.put_stake_account(target, target_account)?;
+
+                eas.amount = eas.amount - amount_requested;
+                if eas.amount == AmountType::from(0) {
+                    store.delete_escrow_account(&id)?;
+                    store.remove_active_escrow_id(&eas.owner, &id)?;
+                } else {
+                    store.put_escrow_account(id, eas)?;
+                }
+
+                Ok(())
+            })
+            .map(|_| amount_requested);
+        if result.is_ok() {
+            let remaining = remaining_escrow_amount(&*self.store, &id);
+            self.events.notify(&StakeEvent::EscrowTaken {
+                id,
+                target,
+                taken: amount_requested,
+                remaining,
+            });
+        }
+        Box::new(future::result(result))
+    }
+
+    fn slash_escrow(&self, caller: B256, id: B256, amount: AmountType) -> BoxFuture<AmountType> {
+        if caller != self.meta.lock().unwrap().slashing_authority {
+            return Box::new(future::result(Err(Error::new(
+                ErrorCodes::CallerNotSlashingAuthority.to_string(),
+            ))));
+        }
+
+        let mut slashed_owner = None;
+        let result = self
+            .store
+            .transaction(&mut |store| {
+                let mut eas = get_escrow(store, &id)?;
+                let owner = eas.owner;
+
+                if amount > eas.amount {
+                    return Err(Error::new(
+                        ErrorCodes::RequestExceedsEscrowedFunds.to_string(),
+                    ));
+                }
+
+                let mut owner_account = get_account(store, owner)?;
+                owner_account.total_stake = owner_account.total_stake - amount;
+                owner_account.escrowed = owner_account.escrowed - amount;
+                store.put_stake_account(owner, owner_account)?;
+
+                eas.amount = eas.amount - amount;
+                if eas.amount == AmountType::from(0) {
+                    store.delete_escrow_account(&id)?;
+                    store.remove_active_escrow_id(&owner, &id)?;
+                } else {
+                    store.put_escrow_account(id, eas)?;
+                }
+
+                slashed_owner = Some(owner);
+                Ok(())
+            })
+            .map(|_| {
+                // Burn the slashed tokens outright rather than crediting
+                // any target -- slashing punishes the owner, it does not
+                // pay out.
+                let mut meta = self.meta.lock().unwrap();
+                meta.total_supply = meta.total_supply - amount;
+                (amount, slashed_owner.unwrap())
+            });
+        if let Ok((amount, owner)) = &result {
+            self.events.notify(&StakeEvent::Burn {
+                from: *owner,
+                amount: *amount,
+            });
+        }
+        Box::new(future::result(result.map(|(amount, _owner)| amount)))
+    }
+
+    fn watch_events(&self) -> BoxStream<StakeEvent> {
+        self.events.subscribe().1
+    }
+
+    fn get_accumulated_rewards(&self, owner: B256) -> BoxFuture<AmountType> {
+        let rewards = self.rewards.lock().unwrap();
+        Box::new(future::ok(
+            rewards.get(&owner).cloned().unwrap_or(AmountType::from(0)),
+        ))
+    }
+
+    fn set_withdrawal_limit(&self, owner: B256, limit: Option<u64>) -> BoxFuture<()> {
+        let result = (|| -> Result<()> {
+            let mut account = get_account(&*self.store, owner)?;
+            account.withdrawal_limit = limit;
+            self.store.put_stake_account(owner, account)
+        })();
+        Box::new(future::result(result))
+    }
+}
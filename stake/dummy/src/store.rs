@@ -0,0 +1,221 @@
+//! In-memory `StakeEscrowStore` implementation.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use ekiden_common::bytes::B256;
+use ekiden_common::error::Result;
+use ekiden_stake_base::{AmountType, EscrowAccount, StakeAccount, StakeEscrowStore};
+
+#[derive(Default)]
+struct MapStoreInner {
+    stake_accounts: HashMap<B256, StakeAccount>,
+    escrow_accounts: HashMap<B256, EscrowAccount>,
+    escrows_by_owner: HashMap<B256, Vec<B256>>,
+    allowances: HashMap<(B256, B256), AmountType>,
+}
+
+impl MapStoreInner {
+    fn get_stake_account(&self, id: &B256) -> Result<Option<StakeAccount>> {
+        Ok(self.stake_accounts.get(id).cloned())
+    }
+
+    fn put_stake_account(&mut self, id: B256, account: StakeAccount) -> Result<()> {
+        self.stake_accounts.insert(id, account);
+        Ok(())
+    }
+
+    fn get_escrow_account(&self, id: &B256) -> Result<Option<EscrowAccount>> {
+        Ok(self.escrow_accounts.get(id).cloned())
+    }
+
+    fn put_escrow_account(&mut self, id: B256, account: EscrowAccount) -> Result<()> {
+        self.escrow_accounts.insert(id, account);
+        Ok(())
+    }
+
+    fn delete_escrow_account(&mut self, id: &B256) -> Result<()> {
+        self.escrow_accounts.remove(id);
+        Ok(())
+    }
+
+    fn get_allowance(&self, owner: &B256, spender: &B256) -> Result<Option<AmountType>> {
+        Ok(self.allowances.get(&(*owner, *spender)).cloned())
+    }
+
+    fn put_allowance(&mut self, owner: B256, spender: B256, amount: AmountType) -> Result<()> {
+        self.allowances.insert((owner, spender), amount);
+        Ok(())
+    }
+
+    fn list_active_escrow_ids(&self, owner: &B256) -> Result<Vec<B256>> {
+        Ok(self
+            .escrows_by_owner
+            .get(owner)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn add_active_escrow_id(&mut self, owner: B256, id: B256) -> Result<()> {
+        self.escrows_by_owner
+            .entry(owner)
+            .or_insert_with(Vec::new)
+            .push(id);
+        Ok(())
+    }
+
+    fn remove_active_escrow_id(&mut self, owner: &B256, id: &B256) -> Result<()> {
+        if let Some(ids) = self.escrows_by_owner.get_mut(owner) {
+            ids.retain(|eid| eid != id);
+        }
+        Ok(())
+    }
+
+    fn list_escrowed_accounts(&self) -> Result<Vec<B256>> {
+        Ok(self
+            .stake_accounts
+            .iter()
+            .filter(|&(_, account)| account.escrowed > AmountType::from(0))
+            .map(|(owner, _)| *owner)
+            .collect())
+    }
+}
+
+/// A `StakeEscrowStore` backed by in-process `HashMap`s.
+///
+/// Used by `DummyStakeEscrowBackend` by default; state does not survive a
+/// process restart. Every operation is infallible in practice, but still
+/// returns a `Result` to satisfy the `StakeEscrowStore` contract so that
+/// backends written against it also work against a store that can fail.
+#[derive(Default)]
+pub struct MapStakeEscrowStore {
+    inner: Mutex<MapStoreInner>,
+}
+
+impl MapStakeEscrowStore {
+    /// Construct an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StakeEscrowStore for MapStakeEscrowStore {
+    fn get_stake_account(&self, id: &B256) -> Result<Option<StakeAccount>> {
+        self.inner.lock().unwrap().get_stake_account(id)
+    }
+
+    fn put_stake_account(&self, id: B256, account: StakeAccount) -> Result<()> {
+        self.inner.lock().unwrap().put_stake_account(id, account)
+    }
+
+    fn get_escrow_account(&self, id: &B256) -> Result<Option<EscrowAccount>> {
+        self.inner.lock().unwrap().get_escrow_account(id)
+    }
+
+    fn put_escrow_account(&self, id: B256, account: EscrowAccount) -> Result<()> {
+        self.inner.lock().unwrap().put_escrow_account(id, account)
+    }
+
+    fn delete_escrow_account(&self, id: &B256) -> Result<()> {
+        self.inner.lock().unwrap().delete_escrow_account(id)
+    }
+
+    fn get_allowance(&self, owner: &B256, spender: &B256) -> Result<Option<AmountType>> {
+        self.inner.lock().unwrap().get_allowance(owner, spender)
+    }
+
+    fn put_allowance(&self, owner: B256, spender: B256, amount: AmountType) -> Result<()> {
+        self.inner
+            .lock()
+            .unwrap()
+            .put_allowance(owner, spender, amount)
+    }
+
+    fn list_active_escrow_ids(&self, owner: &B256) -> Result<Vec<B256>> {
+        self.inner.lock().unwrap().list_active_escrow_ids(owner)
+    }
+
+    fn add_active_escrow_id(&self, owner: B256, id: B256) -> Result<()> {
+        self.inner.lock().unwrap().add_active_escrow_id(owner, id)
+    }
+
+    fn remove_active_escrow_id(&self, owner: &B256, id: &B256) -> Result<()> {
+        self.inner
+            .lock()
+            .unwrap()
+            .remove_active_escrow_id(owner, id)
+    }
+
+    fn list_escrowed_accounts(&self) -> Result<Vec<B256>> {
+        self.inner.lock().unwrap().list_escrowed_accounts()
+    }
+
+    fn transaction(&self, f: &mut FnMut(&StakeEscrowStore) -> Result<()>) -> Result<()> {
+        let mut guard = self.inner.lock().unwrap();
+        let locked = LockedStore {
+            inner: RefCell::new(&mut *guard),
+        };
+        f(&locked)
+    }
+}
+
+/// A view over a `MapStoreInner` that is already locked by an in-progress
+/// `transaction`, so the closure passed to `transaction` can call back
+/// into the same `StakeEscrowStore` methods without trying to re-acquire
+/// `MapStakeEscrowStore`'s mutex (which would deadlock, since a
+/// `std::sync::Mutex` is not reentrant).
+struct LockedStore<'a> {
+    inner: RefCell<&'a mut MapStoreInner>,
+}
+
+impl<'a> StakeEscrowStore for LockedStore<'a> {
+    fn get_stake_account(&self, id: &B256) -> Result<Option<StakeAccount>> {
+        self.inner.borrow().get_stake_account(id)
+    }
+
+    fn put_stake_account(&self, id: B256, account: StakeAccount) -> Result<()> {
+        self.inner.borrow_mut().put_stake_account(id, account)
+    }
+
+    fn get_escrow_account(&self, id: &B256) -> Result<Option<EscrowAccount>> {
+        self.inner.borrow().get_escrow_account(id)
+    }
+
+    fn put_escrow_account(&self, id: B256, account: EscrowAccount) -> Result<()> {
+        self.inner.borrow_mut().put_escrow_account(id, account)
+    }
+
+    fn delete_escrow_account(&self, id: &B256) -> Result<()> {
+        self.inner.borrow_mut().delete_escrow_account(id)
+    }
+
+    fn get_allowance(&self, owner: &B256, spender: &B256) -> Result<Option<AmountType>> {
+        self.inner.borrow().get_allowance(owner, spender)
+    }
+
+    fn put_allowance(&self, owner: B256, spender: B256, amount: AmountType) -> Result<()> {
+        self.inner.borrow_mut().put_allowance(owner, spender, amount)
+    }
+
+    fn list_active_escrow_ids(&self, owner: &B256) -> Result<Vec<B256>> {
+        self.inner.borrow().list_active_escrow_ids(owner)
+    }
+
+    fn add_active_escrow_id(&self, owner: B256, id: B256) -> Result<()> {
+        self.inner.borrow_mut().add_active_escrow_id(owner, id)
+    }
+
+    fn remove_active_escrow_id(&self, owner: &B256, id: &B256) -> Result<()> {
+        self.inner.borrow_mut().remove_active_escrow_id(owner, id)
+    }
+
+    fn list_escrowed_accounts(&self) -> Result<Vec<B256>> {
+        self.inner.borrow().list_escrowed_accounts()
+    }
+
+    fn transaction(&self, f: &mut FnMut(&StakeEscrowStore) -> Result<()>) -> Result<()> {
+        // Already inside a transaction against the same lock -- just run
+        // `f` directly rather than trying to take the lock again.
+        f(self)
+    }
+}
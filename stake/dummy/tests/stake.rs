@@ -545,3 +545,337 @@ fn test_dummy_stake_backend() {
         carol_stake + ok_transfer
     );
 }
+
+#[test]
+fn test_slash_escrow() {
+    try_init_logging();
+
+    let mut id_generator = IdGenerator::new();
+    let oasis = id_generator.gen_id();
+
+    let initial_total_tokens = AmountType::from(1);
+    let initial_total_supply =
+        initial_total_tokens * AmountType::from(1_000_000_000_000_000_000u64);
+
+    let backend = Arc::new(DummyStakeEscrowBackend::new(
+        oasis,
+        "EkidenStake".to_string(),
+        "E$".to_string(),
+        initial_total_tokens,
+    ));
+
+    let alice = id_generator.gen_id();
+    backend
+        .transfer(oasis, alice, AmountType::from(100))
+        .wait()
+        .unwrap();
+
+    let bob = id_generator.gen_id();
+    let bob_aux = id_generator.gen_id();
+    let escrow_id = backend
+        .allocate_escrow(alice, bob, AmountType::from(20), bob_aux)
+        .wait()
+        .unwrap();
+
+    debug!("a non-authority attempting to slash should fail");
+    match backend
+        .slash_escrow(alice, escrow_id, AmountType::from(5))
+        .wait()
+    {
+        Err(e) => assert_eq!(
+            e.message,
+            ErrorCodes::CallerNotSlashingAuthority.to_string()
+        ),
+        Ok(v) => {
+            error!("slash by non-authority succeeded with {}", v);
+            assert!(false);
+        }
+    }
+
+    debug!("slashing more than the escrowed amount should fail");
+    match backend
+        .slash_escrow(oasis, escrow_id, AmountType::from(100))
+        .wait()
+    {
+        Err(e) => assert_eq!(
+            e.message,
+            ErrorCodes::RequestExceedsEscrowedFunds.to_string()
+        ),
+        Ok(v) => {
+            error!("slash exceeding escrow succeeded with {}", v);
+            assert!(false);
+        }
+    }
+
+    debug!("slashing part of the escrow burns tokens and leaves it open");
+    let slashed = backend
+        .slash_escrow(oasis, escrow_id, AmountType::from(8))
+        .wait()
+        .unwrap();
+    assert_eq!(slashed, AmountType::from(8));
+
+    let stake_status = backend.get_stake_status(alice).wait().unwrap();
+    assert_eq!(stake_status.total_stake, AmountType::from(100 - 8));
+    assert_eq!(stake_status.escrowed, AmountType::from(20 - 8));
+
+    let total_supply = backend.get_total_supply().wait().unwrap();
+    assert_eq!(total_supply, initial_total_supply - AmountType::from(8));
+
+    let eas = backend.fetch_escrow_by_id(escrow_id).wait().unwrap();
+    assert_eq!(eas.amount, AmountType::from(20 - 8));
+
+    debug!("slashing the remainder releases the escrow");
+    backend
+        .slash_escrow(oasis, escrow_id, AmountType::from(12))
+        .wait()
+        .unwrap();
+    match backend.fetch_escrow_by_id(escrow_id).wait() {
+        Err(e) => assert_eq!(e.message, ErrorCodes::NoEscrowAccount.to_string()),
+        Ok(_) => {
+            error!("fully slashed escrow should no longer exist");
+            assert!(false);
+        }
+    }
+
+    let stake_status = backend.get_stake_status(alice).wait().unwrap();
+    assert_eq!(stake_status.total_stake, AmountType::from(100 - 20));
+    assert_eq!(stake_status.escrowed, AmountType::from(0));
+}
+
+#[test]
+fn test_epoch_inflation() {
+    try_init_logging();
+
+    let mut id_generator = IdGenerator::new();
+    let oasis = id_generator.gen_id();
+
+    let initial_total_tokens = AmountType::from(1_000);
+
+    // 1% inflation per epoch, to keep the numbers easy to follow.
+    let backend = Arc::new(DummyStakeEscrowBackend::new_with_inflation(
+        oasis,
+        oasis,
+        "EkidenStake".to_string(),
+        "E$".to_string(),
+        initial_total_tokens,
+        (1, 100),
+    ));
+
+    let total_supply_before = backend.get_total_supply().wait().unwrap();
+
+    let alice = id_generator.gen_id();
+    let bob = id_generator.gen_id();
+    backend
+        .transfer(oasis, alice, AmountType::from(300))
+        .wait()
+        .unwrap();
+    backend
+        .transfer(oasis, bob, AmountType::from(100))
+        .wait()
+        .unwrap();
+
+    // Alice escrows 3x what Bob escrows, so she should get 3x the reward.
+    let aux = id_generator.gen_id();
+    backend
+        .allocate_escrow(alice, oasis, AmountType::from(300), aux)
+        .wait()
+        .unwrap();
+    backend
+        .allocate_escrow(bob, oasis, AmountType::from(100), aux)
+        .wait()
+        .unwrap();
+
+    backend.notify_new_epoch().unwrap();
+
+    let minted = total_supply_before / AmountType::from(100);
+    let total_supply_after = backend.get_total_supply().wait().unwrap();
+    assert_eq!(total_supply_after, total_supply_before + minted);
+
+    let alice_reward = backend.get_accumulated_rewards(alice).wait().unwrap();
+    let bob_reward = backend.get_accumulated_rewards(bob).wait().unwrap();
+    assert_eq!(alice_reward, AmountType::from(3) * bob_reward);
+    // Rewards credited should never exceed what was actually minted.
+    assert!(alice_reward + bob_reward <= minted);
+
+    debug!(
+        "epoch 1: minted {}, alice reward {}, bob reward {}",
+        minted, alice_reward, bob_reward
+    );
+
+    debug!("an account with no escrow should accrue no reward");
+    let carol = id_generator.gen_id();
+    backend
+        .transfer(oasis, carol, AmountType::from(50))
+        .wait()
+        .unwrap();
+    backend.notify_new_epoch().unwrap();
+    assert_eq!(
+        backend.get_accumulated_rewards(carol).wait().unwrap(),
+        AmountType::from(0)
+    );
+}
+
+#[test]
+fn test_escrow_limits() {
+    try_init_logging();
+
+    let mut id_generator = IdGenerator::new();
+    let oasis = id_generator.gen_id();
+
+    // At most 2 active escrows, and no more than half of total_stake may
+    // ever be escrowed at once.
+    let backend = Arc::new(DummyStakeEscrowBackend::new_with_limits(
+        oasis,
+        oasis,
+        "EkidenStake".to_string(),
+        "E$".to_string(),
+        AmountType::from(1_000),
+        Some(2),
+        Some((1, 2)),
+    ));
+
+    let alice = id_generator.gen_id();
+    backend
+        .transfer(oasis, alice, AmountType::from(100))
+        .wait()
+        .unwrap();
+    let target = id_generator.gen_id();
+    let aux = id_generator.gen_id();
+
+    let stake_status = backend.get_stake_status(alice).wait().unwrap();
+    assert_eq!(stake_status.escrow_headroom, Some(AmountType::from(50)));
+
+    debug!("escrowing 60 -- exceeds the 50% cap, should fail");
+    match backend
+        .allocate_escrow(alice, target, AmountType::from(60), aux)
+        .wait()
+    {
+        Err(e) => {
+            debug!("Got error {}", e.message);
+            assert_eq!(e.message, ErrorCodes::EscrowCapExceeded.to_string());
+        }
+        Ok(id) => {
+            error!(
+                "Got escrow id {} when allocate_escrow should have failed (EscrowCapExceeded)",
+                id
+            );
+            assert!(false);
+        }
+    }
+
+    backend
+        .allocate_escrow(alice, target, AmountType::from(20), aux)
+        .wait()
+        .unwrap();
+    backend
+        .allocate_escrow(alice, target, AmountType::from(20), aux)
+        .wait()
+        .unwrap();
+
+    let stake_status = backend.get_stake_status(alice).wait().unwrap();
+    assert_eq!(stake_status.escrow_headroom, Some(AmountType::from(10)));
+
+    debug!("a third escrow -- exceeds the cap of 2 active escrows, should fail");
+    match backend
+        .allocate_escrow(alice, target, AmountType::from(5), aux)
+        .wait()
+    {
+        Err(e) => {
+            debug!("Got error {}", e.message);
+            assert_eq!(e.message, ErrorCodes::TooManyActiveEscrows.to_string());
+        }
+        Ok(id) => {
+            error!(
+                "Got escrow id {} when allocate_escrow should have failed (TooManyActiveEscrows)",
+                id
+            );
+            assert!(false);
+        }
+    }
+}
+
+#[test]
+fn test_amount_parsing_and_formatting() {
+    try_init_logging();
+
+    assert_eq!(
+        parse_amount("1.5", 18).unwrap(),
+        AmountType::from(1_500_000_000_000_000_000u64)
+    );
+    assert_eq!(parse_amount("42", 18).unwrap(), AmountType::from(42) * AmountType::from(1_000_000_000_000_000_000u64));
+    assert_eq!(
+        parse_amount("0.000000000000000001", 18).unwrap(),
+        AmountType::from(1)
+    );
+
+    debug!("an over-precise amount should be rejected");
+    assert!(parse_amount("1.0000000000000000001", 18).is_err());
+
+    assert_eq!(
+        format_amount(AmountType::from(1_500_000_000_000_000_000u64), 18),
+        "1.5"
+    );
+    assert_eq!(
+        format_amount(
+            AmountType::from(42) * AmountType::from(1_000_000_000_000_000_000u64),
+            18
+        ),
+        "42"
+    );
+    assert_eq!(format_amount(AmountType::from(1), 18), "0.000000000000000001");
+}
+
+#[test]
+fn test_withdrawal_limit() {
+    try_init_logging();
+
+    let mut id_generator = IdGenerator::new();
+    let oasis = id_generator.gen_id();
+
+    let backend = Arc::new(DummyStakeEscrowBackend::new(
+        oasis,
+        "EkidenStake".to_string(),
+        "E$".to_string(),
+        AmountType::from(1_000),
+    ));
+
+    let alice = id_generator.gen_id();
+    backend
+        .transfer(oasis, alice, parse_amount("500", 18).unwrap())
+        .wait()
+        .unwrap();
+
+    // Alice may withdraw at most 10 whole tokens at a time.
+    backend
+        .set_withdrawal_limit(alice, Some(10))
+        .wait()
+        .unwrap();
+
+    let bob = id_generator.gen_id();
+    debug!("transferring 11 tokens -- exceeds the 10 token withdrawal limit, should fail");
+    match backend
+        .transfer(
+            alice,
+            bob,
+            parse_amount("11", 18).unwrap(),
+        )
+        .wait()
+    {
+        Err(e) => {
+            debug!("Got error {}", e.message);
+            assert_eq!(e.message, ErrorCodes::WithdrawalLimitExceeded.to_string());
+        }
+        Ok(_) => {
+            error!("Transfer should have failed (WithdrawalLimitExceeded)");
+            assert!(false);
+        }
+    }
+
+    backend
+        .transfer(alice, bob, parse_amount("10", 18).unwrap())
+        .wait()
+        .unwrap();
+
+    let bob_balance = backend.balance_of(bob).wait().unwrap();
+    assert_eq!(format_amount(bob_balance, 18), "10");
+}